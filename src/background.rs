@@ -0,0 +1,170 @@
+use crate::prelude::*;
+
+use std::f32::consts::PI;
+
+// Background:
+//
+// radiance arriving from infinitely far away, for rays that escape the scene
+// `dir` is normalized
+//
+pub trait Background: Send + Sync {
+    fn radiance(&self, dir: &Vector) -> Color;
+
+    // whether sample can be used for next-event estimation
+    fn is_light(&self) -> bool {
+        false
+    }
+
+    // a direction toward the background and its probability density by solid angle
+    fn sample(&self, _sampler: &mut Sampler) -> Option<(Vector, Scalar)> {
+        None
+    }
+
+    fn pdf(&self, _dir: &Vector) -> Scalar {
+        0.
+    }
+}
+
+impl<B: Background + ?Sized> Background for Box<B> {
+    fn radiance(&self, dir: &Vector) -> Color {
+        (**self).radiance(dir)
+    }
+
+    fn is_light(&self) -> bool {
+        (**self).is_light()
+    }
+
+    fn sample(&self, sampler: &mut Sampler) -> Option<(Vector, Scalar)> {
+        (**self).sample(sampler)
+    }
+
+    fn pdf(&self, dir: &Vector) -> Scalar {
+        (**self).pdf(dir)
+    }
+}
+
+impl Background for Color {
+    fn radiance(&self, _: &Vector) -> Color {
+        *self
+    }
+}
+
+// bottom to top
+impl Background for palette::Gradient<Color> {
+    fn radiance(&self, dir: &Vector) -> Color {
+        self.get((dir.y + 1.) / 2.)
+    }
+}
+
+// Environment:
+//
+// a latitude-longitude image wrapped around the scene, the center column looks down -z
+// and the top row straight up, `rotation` turns the image before lookups
+// directions are sampled proportionally to the luminance of the texels they land on
+//
+pub struct Environment {
+    image: Framebuffer,
+    rotation: na::UnitQuaternion<Scalar>,
+    intensity: Scalar,
+    distribution: Distribution2D,
+}
+
+impl Environment {
+    pub fn new(
+        image: Framebuffer,
+        rotation: na::UnitQuaternion<Scalar>,
+        intensity: Scalar,
+    ) -> Self {
+        // texels shrink toward the poles, the weights follow the solid angle they cover
+        let distribution = Distribution2D::new(image.width, image.height, |x, y| {
+            let elevation = (0.5 - (y as Scalar + 0.5) / image.height as Scalar) * PI;
+            luminance(image.get(x, y)) * elevation.cos()
+        });
+
+        Self {
+            image: image,
+            rotation: rotation,
+            intensity: intensity,
+            distribution: distribution,
+        }
+    }
+
+    // image coordinates of `dir`, v goes up
+    pub fn uv(&self, dir: &Vector) -> (Scalar, Scalar) {
+        let local = self.rotation.inverse() * dir;
+        let u = 0.5 + local.x.atan2(-local.z) / (2. * PI);
+        let v = 0.5 + na::clamp(local.y, -1., 1.).asin() / PI;
+        (u, v)
+    }
+
+    // inverse of uv
+    pub fn dir(&self, u: Scalar, v: Scalar) -> Vector {
+        let longitude = (u - 0.5) * 2. * PI;
+        let elevation = (v - 0.5) * PI;
+        let local = Vector::new(
+            elevation.cos() * longitude.sin(),
+            elevation.sin(),
+            -elevation.cos() * longitude.cos(),
+        );
+        self.rotation * local
+    }
+}
+
+impl Background for Environment {
+    fn radiance(&self, dir: &Vector) -> Color {
+        let (u, v) = self.uv(dir);
+        self.image
+            .lookup(u, v, Filter::Bilinear, (Wrap::Repeat, Wrap::Clamp))
+            * self.intensity
+    }
+
+    fn is_light(&self) -> bool {
+        self.distribution.total() > 0.
+    }
+
+    fn sample(&self, sampler: &mut Sampler) -> Option<(Vector, Scalar)> {
+        if !self.is_light() {
+            return None;
+        }
+
+        let (x, y) = self.distribution.sample(sampler.get_2d());
+
+        let offset = sampler.get_2d();
+        let u = (x as Scalar + offset.x) / self.image.width as Scalar;
+        let v = 1. - (y as Scalar + offset.y) / self.image.height as Scalar;
+        let dir = self.dir(u, v);
+        let pdf = self.pdf(&dir);
+
+        if pdf > 0. {
+            Some((dir, pdf))
+        } else {
+            None
+        }
+    }
+
+    fn pdf(&self, dir: &Vector) -> Scalar {
+        if !self.is_light() {
+            return 0.;
+        }
+
+        let (u, v) = self.uv(dir);
+        let x = na::clamp(
+            (u * self.image.width as Scalar) as u32,
+            0,
+            self.image.width - 1,
+        );
+        let y = na::clamp(
+            ((1. - v) * self.image.height as Scalar) as u32,
+            0,
+            self.image.height - 1,
+        );
+
+        let weight = self.distribution.weight(x, y);
+        let texels = (self.image.width * self.image.height) as Scalar;
+        let elevation = (v - 0.5) * PI;
+
+        // density over the image, then over the sphere
+        let pdf_uv = weight * texels / self.distribution.total();
+        pdf_uv / (2. * PI * PI * elevation.cos())
+    }
+}
@@ -1,5 +1,10 @@
 use crate::prelude::*;
 
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+const CHECKPOINT_MAGIC: &[u8; 8] = b"in1wckp2";
+
 #[derive(Debug)]
 pub struct Camera {
     origin: Point,
@@ -9,11 +14,111 @@ pub struct Camera {
     u: Vector,
     v: Vector,
 
+    projection: Projection,
+    stereo: Option<Stereo>,
     lens_radius: Scalar,
+    aperture_shape: ApertureShape,
+    cat_eye: Scalar,
+    // seconds, motion is given over the whole exposure so rays only carry a fraction of it
+    // and 0 or less is an instant, when the shutter opens, without any motion blur
     shutter_speed: Scalar,
 
     resolution: na::Vector2<u32>,
     samples: u32,
+    tone_map: Box<ToneMap>,
+    seed: u64,
+    sampler: SamplerKind,
+    adaptive: Option<Adaptive>,
+    tile_size: u32,
+    tile_order: TileOrder,
+    threads: usize,
+}
+
+// how rays leave the camera
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    // from the lens through the view rectangle
+    Perspective,
+    // from every point of the view rectangle, straight ahead
+    Orthographic,
+    // all around, longitude across and latitude down, the center of the image looks ahead
+    // images twice as wide as they're high keep texels square
+    Equirectangular,
+    // angular, the angle from the view direction grows with the distance from the center
+    // `fov` in degrees spans the shorter side, anything outside of that circle is black
+    Fisheye { fov: Scalar },
+}
+
+// the shape light goes through the lens in, which out of focus highlights take
+#[derive(Clone, Debug)]
+pub enum ApertureShape {
+    Circle,
+    // regular, fit in the circle with a corner at the top, before `rotation` in degrees
+    // turns it counterclockwise
+    Polygon { blades: u32, rotation: Scalar },
+    Mask(Arc<ApertureMask>),
+}
+
+// an image stretched over the square around the lens,
+// light goes through in proportion to its luminance
+#[derive(Debug)]
+pub struct ApertureMask {
+    width: u32,
+    height: u32,
+    distribution: Distribution2D,
+}
+
+impl ApertureMask {
+    pub fn new(image: &Framebuffer) -> Self {
+        Self {
+            width: image.width,
+            height: image.height,
+            distribution: Distribution2D::new(image.width, image.height, |x, y| {
+                luminance(image.get(x, y))
+            }),
+        }
+    }
+
+    // on the lens, between -1 and 1, nothing getting through makes it a pinhole
+    fn sample(&self, sampler: &mut Sampler) -> Vector2 {
+        if self.distribution.total() <= 0. {
+            return Vector2::zeros();
+        }
+
+        let (x, y) = self.distribution.sample(sampler.get_2d());
+        let offset = sampler.get_2d();
+        Vector2::new(
+            (x as Scalar + offset.x) / self.width as Scalar * 2. - 1.,
+            1. - (y as Scalar + offset.y) / self.height as Scalar * 2.,
+        )
+    }
+}
+
+// both eyes rendered into one image, `interocular` apart,
+// looking parallel or converging at `convergence` in front of the camera
+#[derive(Clone, Copy, Debug)]
+pub struct Stereo {
+    pub interocular: Scalar,
+    pub convergence: Option<Scalar>,
+    pub layout: StereoLayout,
+}
+
+// where the left eye goes, the right one takes the other half
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StereoLayout {
+    // left
+    SideBySide,
+    // top
+    TopBottom,
+}
+
+// pixels stop taking samples once the standard error of their luminance,
+// relative to the luminance itself, drops below `threshold`
+// the variance is checked every `min_samples` samples
+#[derive(Clone, Copy, Debug)]
+pub struct Adaptive {
+    pub min_samples: u32,
+    pub threshold: Scalar,
 }
 
 impl Camera {
@@ -43,107 +148,742 @@ impl Camera {
             vertical: 2. * half_height * focus_dist * v,
             u: u,
             v: v,
+            projection: Projection::Perspective,
+            stereo: None,
             lens_radius: aperture / 2.,
+            aperture_shape: ApertureShape::Circle,
+            cat_eye: 0.,
             shutter_speed: shutter_speed,
             resolution: resolution,
             samples: samples,
+            tone_map: Box::new(Clamp),
+            seed: 0,
+            sampler: SamplerKind::default(),
+            adaptive: None,
+            tile_size: 32,
+            tile_order: TileOrder::default(),
+            threads: 0,
+        }
+    }
+
+    // parallel rays, `width` is the size of the view across, the height follows the aspect ratio
+    // there's no depth of field
+    pub fn orthographic(
+        from: &Point,
+        at: &Point,
+        up: &Vector,
+        width: Scalar,
+        shutter_speed: Scalar,
+        resolution: na::Vector2<u32>,
+        samples: u32,
+    ) -> Self {
+        let mut camera = Self::new(
+            from,
+            at,
+            up,
+            90.,
+            0.,
+            None,
+            shutter_speed,
+            resolution,
+            samples,
+        );
+        let height = width * resolution.y as Scalar / resolution.x as Scalar;
+
+        camera.horizontal = width * camera.u;
+        camera.vertical = height * camera.v;
+        camera.with_projection(Projection::Orthographic)
+    }
+
+    // the view rectangle of a perspective camera, where it's in focus, becomes the view
+    // of an orthographic one, panoramic projections only keep the position and orientation
+    // neither has depth of field
+    pub fn with_projection(mut self, projection: Projection) -> Self {
+        if projection == Projection::Orthographic {
+            self.top_left_corner = self.origin - self.horizontal / 2. + self.vertical / 2.;
+        }
+        if projection != Projection::Perspective {
+            self.lens_radius = 0.;
+        }
+        self.projection = projection;
+        self
+    }
+
+    // keeps the vertical field of view, the horizontal one follows the new aspect ratio
+    pub fn with_resolution(mut self, resolution: na::Vector2<u32>) -> Self {
+        let center = self.top_left_corner + self.horizontal / 2. - self.vertical / 2.;
+        let aspect = |r: na::Vector2<u32>| r.x as Scalar / r.y as Scalar;
+
+        self.horizontal *= aspect(resolution) / aspect(self.resolution);
+        self.top_left_corner = center - self.horizontal / 2. + self.vertical / 2.;
+        self.resolution = resolution;
+        self
+    }
+
+    // bokeh, the aperture passed to new still sets the lens' size
+    pub fn with_aperture_shape(mut self, shape: ApertureShape) -> Self {
+        self.aperture_shape = shape;
+        self
+    }
+
+    // optical vignetting, the lens barrel cuts into the aperture further from the center
+    // of the image, out of focus highlights turn into cat's eyes and the edges darken
+    // `strength` is how far across it has moved at the top and bottom edges, in lens radii
+    pub fn with_cat_eye(mut self, strength: Scalar) -> Self {
+        self.cat_eye = strength;
+        self
+    }
+
+    // the resolution stays that of each eye, images get twice as large
+    pub fn with_stereo(mut self, stereo: Option<Stereo>) -> Self {
+        self.stereo = stereo;
+        self
+    }
+
+    pub fn with_samples(mut self, samples: u32) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn with_tone_map(mut self, tone_map: Box<ToneMap>) -> Self {
+        self.tone_map = tone_map;
+        self
+    }
+
+    // the same seed renders the same image, whatever the thread count
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_sampler(mut self, sampler: SamplerKind) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    // `samples` becomes the most a pixel can take
+    pub fn with_adaptive(mut self, adaptive: Option<Adaptive>) -> Self {
+        self.adaptive = adaptive;
+        self
+    }
+
+    pub fn with_tiles(mut self, size: u32, order: TileOrder) -> Self {
+        self.tile_size = size;
+        self.tile_order = order;
+        self
+    }
+
+    // 0 uses every core
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    pub fn stereo(&self) -> Option<Stereo> {
+        self.stereo
+    }
+
+    // of one eye's view with stereo
+    pub fn resolution(&self) -> na::Vector2<u32> {
+        self.resolution
+    }
+
+    // of what's rendered, both eyes with stereo
+    pub fn image_size(&self) -> na::Vector2<u32> {
+        match self.stereo.map(|stereo| stereo.layout) {
+            Some(StereoLayout::SideBySide) => {
+                na::Vector2::new(self.resolution.x * 2, self.resolution.y)
+            }
+            Some(StereoLayout::TopBottom) => {
+                na::Vector2::new(self.resolution.x, self.resolution.y * 2)
+            }
+            None => self.resolution,
         }
     }
-    pub fn capture(&self, scene: &Scene) -> image::RgbImage {
+
+    pub fn tone_map(&self) -> &ToneMap {
+        &*self.tone_map
+    }
+
+    pub fn capture(
+        &self,
+        scene: &Scene,
+        progress: &ProgressSink,
+        control: &RenderControl,
+    ) -> (image::RgbImage, RenderStats) {
+        let (framebuffer, stats) = self.capture_hdr(scene, progress, control);
+        (framebuffer.to_srgb(self.tone_map()), stats)
+    }
+
+    pub fn capture_hdr(
+        &self,
+        scene: &Scene,
+        progress: &ProgressSink,
+        control: &RenderControl,
+    ) -> (Framebuffer, RenderStats) {
+        self.capture_progressive(scene, self.samples, progress, control, |_| {})
+    }
+
+    // renders the whole frame in passes of `pass_samples` samples per pixel,
+    // `on_pass` gets the image as it stands after each of them
+    // short passes keep the image even when `control` stops the render midway
+    pub fn capture_progressive<F: FnMut(&Framebuffer)>(
+        &self,
+        scene: &Scene,
+        pass_samples: u32,
+        progress: &ProgressSink,
+        control: &RenderControl,
+        mut on_pass: F,
+    ) -> (Framebuffer, RenderStats) {
+        let size = self.image_size();
+        let mut accumulator = Accumulator::new(size.x, size.y);
+        let stats = self.accumulate(
+            scene,
+            &mut accumulator,
+            pass_samples,
+            progress,
+            control,
+            |accumulator| on_pass(&accumulator.to_framebuffer()),
+        );
+        (accumulator.to_framebuffer(), stats)
+    }
+
+    // carries on from whatever samples `accumulator` already holds, in passes,
+    // until every pixel has `samples` of them, has converged, or `control` stops it
+    pub fn accumulate<F: FnMut(&Accumulator)>(
+        &self,
+        scene: &Scene,
+        accumulator: &mut Accumulator,
+        pass_samples: u32,
+        progress: &ProgressSink,
+        control: &RenderControl,
+        mut on_pass: F,
+    ) -> RenderStats {
         let started = std::time::Instant::now();
-        let bar = self.create_progress_bar();
+        let pass_samples = pass_samples.max(1);
+        let first = accumulator
+            .pixels
+            .iter()
+            .map(|p| p.samples)
+            .min()
+            .unwrap_or(0)
+            / pass_samples;
+        let passes = (self.samples + pass_samples - 1) / pass_samples;
+        let size = self.image_size();
+        let tiles = self.tile_order.tiles(size.x, size.y, self.tile_size);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()
+            .expect("couldn't start the render threads");
+        let mut stats = RenderStats::default();
+        let taken = AtomicUsize::new(0);
+        progress.started(
+            tiles.len() as u64 * passes.saturating_sub(first) as u64,
+            passes.saturating_sub(first),
+        );
 
-        let img = image::RgbImage::from_fn(self.resolution.x, self.resolution.y, |x, y| {
-            if x == 0 {
-                bar.inc(1);
-                bar.set_message(&self.samples_per_second(x, y, started.elapsed()));
+        for pass in first + 1..=passes {
+            let until = (pass * pass_samples).min(self.samples);
+            let stopped = AtomicBool::new(false);
+
+            // every thread takes the next tile in order from a shared cursor until there's
+            // none left, and writes it back as soon as it's done
+            let cursor = AtomicUsize::new(0);
+            let shared = Mutex::new((&mut *accumulator, &mut stats));
+            let work = || {
+                while let Some(tile) = tiles.get(cursor.fetch_add(1, Ordering::Relaxed)) {
+                    if stopped.load(Ordering::Relaxed) {
+                        break;
+                    }
+
+                    let mut sampler = self.sampler.create(self.seed, self.samples);
+                    let mut tile_stats = RenderStats::default();
+                    let mut estimates: Vec<Estimate> = {
+                        let shared = shared.lock().unwrap();
+                        tile.pixels().map(|(x, y)| shared.0.get(x, y)).collect()
+                    };
+                    for ((x, y), estimate) in tile.pixels().zip(&mut estimates) {
+                        let samples = taken.load(Ordering::Relaxed);
+                        if control.should_stop(started.elapsed(), samples as u64) {
+                            stopped.store(true, Ordering::Relaxed);
+                            break;
+                        }
+
+                        let before = estimate.samples;
+                        self.capture_pixel(
+                            scene,
+                            x,
+                            y,
+                            estimate,
+                            until,
+                            &mut *sampler,
+                            &mut tile_stats,
+                        );
+                        taken.fetch_add((estimate.samples - before) as usize, Ordering::Relaxed);
+                    }
+
+                    {
+                        let mut shared = shared.lock().unwrap();
+                        shared.1.add(&tile_stats);
+                        for ((x, y), estimate) in tile.pixels().zip(estimates) {
+                            *shared.0.get_mut(x, y) = estimate;
+                        }
+                    }
+                    progress.tile_done(tile, &tile_stats, started.elapsed());
+                }
+            };
+            let work = &work;
+            pool.scope(|scope| {
+                for _ in 0..pool.current_num_threads() {
+                    scope.spawn(move |_| work());
+                }
+            });
+            drop(shared);
+
+            // stopped partway through, the tiles left out stay a pass behind
+            if stopped.into_inner() {
+                break;
             }
-            self.capture_pixel(scene, x, y)
-        });
 
-        bar.finish_with_message(&self.samples_per_second(
-            self.resolution.x,
-            self.resolution.y,
-            started.elapsed(),
-        ));
+            progress.pass_done(pass, started.elapsed());
+            on_pass(accumulator);
+        }
 
-        img
+        stats.wall_time = started.elapsed();
+        progress.finished(&stats);
+        stats
     }
 
-    fn capture_pixel(&self, scene: &Scene, x: u32, y: u32) -> image::Rgb<u8> {
-        use palette::Pixel;
-        use rayon::iter::{IntoParallelIterator, ParallelIterator};
+    // the accumulator, along with what picks the sample streams so a resumed render
+    // carries on with new samples instead of repeating old ones, and how rays leave
+    // the camera so it can't mix two different images
+    // stratified samplers split pixels by the sample count, resuming with another one is
+    // refused, other samplers can resume with more samples than they started with
+    pub fn save_checkpoint<P: AsRef<std::path::Path>>(
+        &self,
+        accumulator: &Accumulator,
+        path: P,
+    ) -> Result<(), failure::Error> {
+        use byteorder::{LittleEndian, WriteBytesExt};
+        use std::io::Write;
+
+        // written aside then moved over, a crash mid-write keeps the previous checkpoint
+        let path = path.as_ref();
+        let partial = path.with_extension("partial");
+        {
+            let mut w = std::io::BufWriter::new(std::fs::File::create(&partial)?);
+            w.write_all(CHECKPOINT_MAGIC)?;
+            w.write_u64::<LittleEndian>(self.seed)?;
+            w.write_u8(self.sampler as u8)?;
+            w.write_u32::<LittleEndian>(self.samples)?;
+            w.write_all(&self.checkpoint_view())?;
+            accumulator.write(&mut w)?;
+            w.flush()?;
+        }
+        std::fs::rename(&partial, path)?;
+        Ok(())
+    }
+
+    pub fn load_checkpoint<P: AsRef<std::path::Path>>(
+        &self,
+        path: P,
+    ) -> Result<Accumulator, failure::Error> {
+        use byteorder::{LittleEndian, ReadBytesExt};
+        use std::io::Read;
+
+        let mut r = std::io::BufReader::new(std::fs::File::open(path)?);
+        let mut magic = [0; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != CHECKPOINT_MAGIC {
+            return Err(failure::format_err!("not a checkpoint"));
+        }
 
-        let color = (0..self.samples)
-            .into_par_iter()
-            .map(|_| {
-                let u = (rand::random::<Scalar>() + x as Scalar) / self.resolution.x as Scalar;
-                let v = (rand::random::<Scalar>() + y as Scalar) / self.resolution.y as Scalar;
-                scene.trace(&self.ray(u, v))
-            })
-            .reduce(|| Color::new(0., 0., 0.), |a, b| a + b)
-            / self.samples as f32;
+        let seed = r.read_u64::<LittleEndian>()?;
+        let sampler = r.read_u8()?;
+        if seed != self.seed || sampler != self.sampler as u8 {
+            return Err(failure::format_err!(
+                "the checkpoint was rendered with another seed or sampler"
+            ));
+        }
 
-        let srgb: palette::Srgb<u8> = palette::Srgb::from_linear(color).into_format();
+        let samples = r.read_u32::<LittleEndian>()?;
+        if self.sampler == SamplerKind::Stratified && samples != self.samples {
+            return Err(failure::format_err!(
+                "the checkpoint was stratified over {} samples, resume with as many",
+                samples
+            ));
+        }
 
-        image::Rgb {
-            data: *srgb.as_raw(),
+        let mut view = self.checkpoint_view();
+        r.read_exact(&mut view)?;
+        if view != self.checkpoint_view() {
+            return Err(failure::format_err!(
+                "the checkpoint was rendered with another projection or stereo setup"
+            ));
         }
+
+        let size = self.image_size();
+        Ok(Accumulator::read(&mut r, size.x, size.y)?)
     }
 
-    fn ray(&self, u: Scalar, v: Scalar) -> Ray {
-        let rd = self.lens_radius * Vector2::random_on_sphere();
-        let offset = self.u * rd.x + self.v * rd.y;
+    // the projection and stereo settings, always as many bytes
+    fn checkpoint_view(&self) -> Vec<u8> {
+        use byteorder::{LittleEndian, WriteBytesExt};
+
+        let mut view = Vec::new();
+        let (projection, fov) = match self.projection {
+            Projection::Perspective => (0, 0.),
+            Projection::Orthographic => (1, 0.),
+            Projection::Equirectangular => (2, 0.),
+            Projection::Fisheye { fov } => (3, fov),
+        };
+        let (layout, interocular, convergence) = match self.stereo {
+            Some(stereo) => (
+                match stereo.layout {
+                    StereoLayout::SideBySide => 1,
+                    StereoLayout::TopBottom => 2,
+                },
+                stereo.interocular,
+                stereo.convergence.unwrap_or(0.),
+            ),
+            None => (0, 0., 0.),
+        };
 
-        Ray {
+        // writing to a Vec can't fail
+        view.write_u8(projection).unwrap();
+        view.write_f32::<LittleEndian>(fov).unwrap();
+        view.write_u8(layout).unwrap();
+        view.write_f32::<LittleEndian>(interocular).unwrap();
+        view.write_f32::<LittleEndian>(convergence).unwrap();
+        view
+    }
+
+    // adds samples to the pixel until it has `until` of them, or until it converges
+    fn capture_pixel(
+        &self,
+        scene: &Scene,
+        x: u32,
+        y: u32,
+        estimate: &mut Estimate,
+        until: u32,
+        sampler: &mut Sampler,
+        stats: &mut RenderStats,
+    ) {
+        let batch = match self.adaptive {
+            Some(adaptive) => adaptive.min_samples.max(2),
+            None => until,
+        };
+
+        // batches end on multiples of `batch` and only there is convergence checked,
+        // whatever samples a pass stops at
+        while estimate.samples < until {
+            if let Some(adaptive) = self.adaptive {
+                if estimate.samples >= batch
+                    && estimate.samples % batch == 0
+                    && estimate.standard_error() <= adaptive.threshold * estimate.mean.max(1e-3)
+                {
+                    break;
+                }
+            }
+
+            let end = (estimate.samples / batch + 1)
+                .saturating_mul(batch)
+                .min(until);
+            for index in estimate.samples..end {
+                let sample = self.capture_sample(scene, x, y, index, sampler, stats);
+                estimate.push(sample);
+            }
+        }
+    }
+
+    fn capture_sample(
+        &self,
+        scene: &Scene,
+        x: u32,
+        y: u32,
+        index: u32,
+        sampler: &mut Sampler,
+        stats: &mut RenderStats,
+    ) -> Color {
+        sampler.start(x, y, index);
+        stats.camera_rays += 1;
+
+        let (eye, x, y) = self.eye(x, y);
+        let jitter = sampler.get_2d();
+        let u = (jitter.x + x as Scalar) / self.resolution.x as Scalar;
+        let v = (jitter.y + y as Scalar) / self.resolution.y as Scalar;
+        match self.ray(u, v, eye, sampler) {
+            Some(ray) => scene.trace(&ray, sampler, stats),
+            None => Color::default(),
+        }
+    }
+
+    // `u` and `v` go right and down across the view of `eye`, -1 for the left, 1 for the right
+    // and 0 without stereo, none where the projection doesn't reach
+    fn ray(&self, u: Scalar, v: Scalar, eye: Scalar, sampler: &mut Sampler) -> Option<Ray> {
+        use std::f32::consts::PI;
+
+        let forward = self.v.cross(&self.u);
+        // drawn either way so the other dimensions stay where they are
+        let time = sampler.get_1d();
+        let time = if self.shutter_speed > 0. { time } else { 0. };
+        let (separation, convergence) = match self.stereo {
+            Some(stereo) => (eye * stereo.interocular / 2., stereo.convergence),
+            None => (0., None),
+        };
+        // from `offset` away from the center, turned in to meet the other eye at the convergence
+        let toward = |offset: Vector, dir: Vector| Ray {
             origin: self.origin + offset,
-            dir: self.top_left_corner + u * self.horizontal
-                - v * self.vertical
-                - self.origin
-                - offset,
-        }
-    }
-
-    fn create_progress_bar(&self) -> indicatif::ProgressBar {
-        let bar = indicatif::ProgressBar::new(self.resolution.y as u64);
-        bar.set_style(
-            indicatif::ProgressStyle::default_bar()
-                .template(concat!(
-                    "{spinner} ",
-                    "{wide_bar} ",
-                    "{percent:>3}%, ",
-                    "Elapsed: {elapsed_precise}, ",
-                    "ETA: {eta_precise}, ",
-                    "Samples per second: {msg} "
-                ))
-                .progress_chars("█▇▆▅▄▃▂▁ "),
-        );
-        bar
-    }
-
-    fn samples_per_second(&self, x: u32, y: u32, elapsed: std::time::Duration) -> String {
-        let seconds: f32 = elapsed.as_secs() as f32 + (elapsed.subsec_nanos() as f32) / 1e9;
-        let samples = ((x + y * self.resolution.y) * self.samples) as f32;
-        let rate = samples / seconds;
-
-        match rate {
-            rate if rate >= 1e+24 => format!("{:.2} yotta", rate / 1e+24),
-            rate if rate >= 1e+21 => format!("{:.2} zetta", rate / 1e+21),
-            rate if rate >= 1e+18 => format!("{:.2} exa  ", rate / 1e+18),
-            rate if rate >= 1e+15 => format!("{:.2} peta ", rate / 1e+15),
-            rate if rate >= 1e+12 => format!("{:.2} tera ", rate / 1e+12),
-            rate if rate >= 1e+09 => format!("{:.2} giga ", rate / 1e+09),
-            rate if rate >= 1e+06 => format!("{:.2} mega ", rate / 1e+06),
-            rate if rate >= 1e+03 => format!("{:.2} kilo ", rate / 1e+03),
-            rate if rate <= 1e-20 => format!("{:.2} yocto", rate / 1e-24),
-            rate if rate <= 1e-18 => format!("{:.2} zepto", rate / 1e-21),
-            rate if rate <= 1e-15 => format!("{:.2} atto ", rate / 1e-18),
-            rate if rate <= 1e-12 => format!("{:.2} femto", rate / 1e-15),
-            rate if rate <= 1e-09 => format!("{:.2} pico ", rate / 1e-12),
-            rate if rate <= 1e-06 => format!("{:.2} nano ", rate / 1e-09),
-            rate if rate <= 1e-03 => format!("{:.2} micro", rate / 1e-06),
-            rate if rate <= 1e-00 => format!("{:.2} milli", rate / 1e-03),
-            _ => format!("{:.2}      ", rate),
+            dir: match convergence {
+                Some(distance) => dir * distance - offset,
+                None => dir,
+            },
+            time: time,
+        };
+
+        match self.projection {
+            Projection::Perspective => {
+                let aperture = self.sample_aperture(sampler);
+                if self.cat_eye > 0. {
+                    // where the pixel is, the top and bottom edges are 1 away from the center
+                    let aspect = self.resolution.x as Scalar / self.resolution.y as Scalar;
+                    let position = Vector2::new((2. * u - 1.) * aspect, 1. - 2. * v);
+                    if (aperture - position * self.cat_eye).norm() > 1. {
+                        return None;
+                    }
+                }
+
+                let rd = self.lens_radius * aperture;
+                let lens = self.u * rd.x + self.v * rd.y;
+
+                // the view rectangle follows the eye, all the way when they look parallel,
+                // not at all when they converge on it
+                let focus = (self.top_left_corner - self.origin).dot(&forward);
+                let follow = match convergence {
+                    Some(distance) => 1. - focus / distance,
+                    None => 1.,
+                };
+                let shift = self.u * separation;
+                let origin = self.origin + shift + lens;
+                let target =
+                    self.top_left_corner + u * self.horizontal - v * self.vertical + shift * follow;
+
+                Some(Ray {
+                    origin: origin,
+                    dir: target - origin,
+                    time: time,
+                })
+            }
+            // the eyes are only shifted, there's no convergence for parallel rays
+            Projection::Orthographic => Some(Ray {
+                origin: self.top_left_corner + u * self.horizontal - v * self.vertical
+                    + self.u * separation,
+                dir: forward,
+                time: time,
+            }),
+            Projection::Equirectangular => {
+                let longitude = (u - 0.5) * 2. * PI;
+                let elevation = (0.5 - v) * PI;
+                let dir = elevation.cos() * (longitude.sin() * self.u + longitude.cos() * forward)
+                    + elevation.sin() * self.v;
+
+                // omni-directional stereo, the eyes circle the center as the view turns,
+                // and come together toward the poles where there's no telling left from right
+                let right = longitude.cos() * self.u - longitude.sin() * forward;
+                Some(toward(right * separation * elevation.cos(), dir))
+            }
+            Projection::Fisheye { fov } => {
+                // centered, 1 at the middle of the closest edges
+                let shorter = self.resolution.x.min(self.resolution.y) as Scalar;
+                let x = (u - 0.5) * self.resolution.x as Scalar / shorter * 2.;
+                let y = (0.5 - v) * self.resolution.y as Scalar / shorter * 2.;
+                let radius = (x * x + y * y).sqrt();
+                if radius > 1. {
+                    return None;
+                }
+
+                let theta = radius * fov.to_radians() / 2.;
+                let phi = y.atan2(x);
+                let dir =
+                    theta.sin() * (phi.cos() * self.u + phi.sin() * self.v) + theta.cos() * forward;
+                Some(toward(self.u * separation, dir))
+            }
         }
     }
+
+    // a point of the lens, the unit circle is its edge
+    fn sample_aperture(&self, sampler: &mut Sampler) -> Vector2 {
+        use std::f32::consts::PI;
+
+        match self.aperture_shape {
+            ApertureShape::Circle => Vector2::random_in_sphere(sampler),
+            ApertureShape::Polygon { blades, rotation } => {
+                // in the triangle between the center and one of the sides, they're all alike
+                let blades = blades.max(3) as Scalar;
+                let u = sampler.get_2d();
+                let side = (u.x * blades).floor().min(blades - 1.);
+                let distance = (u.x * blades - side).sqrt();
+                let corner = |i: Scalar| {
+                    let angle = PI / 2. + rotation.to_radians() + i * 2. * PI / blades;
+                    Vector2::new(angle.cos(), angle.sin())
+                };
+                (corner(side) * (1. - u.y) + corner(side + 1.) * u.y) * distance
+            }
+            ApertureShape::Mask(ref mask) => mask.sample(sampler),
+        }
+    }
+
+    // which eye the pixel of the whole image belongs to, and where it is in that eye's view
+    fn eye(&self, x: u32, y: u32) -> (Scalar, u32, u32) {
+        let side = |index: u32| if index == 0 { -1. } else { 1. };
+        match self.stereo.map(|stereo| stereo.layout) {
+            Some(StereoLayout::SideBySide) => {
+                (side(x / self.resolution.x), x % self.resolution.x, y)
+            }
+            Some(StereoLayout::TopBottom) => {
+                (side(y / self.resolution.y), x, y % self.resolution.y)
+            }
+            None => (0., x, y),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera() -> Camera {
+        Camera::new(
+            &Point::new(0., 0., 0.),
+            &Point::new(0., 0., -1.),
+            &Vector::y(),
+            40.,
+            0.,
+            None,
+            0.,
+            na::Vector2::new(4, 3),
+            16,
+        )
+    }
+
+    fn accumulator() -> Accumulator {
+        let mut accumulator = Accumulator::new(4, 3);
+        for (i, estimate) in accumulator.pixels.iter_mut().enumerate() {
+            estimate.push(Color::new(i as Scalar, 1., 0.));
+        }
+        accumulator
+    }
+
+    // unique to the test and the process, removed once the test is over
+    struct TempPath(std::path::PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> Self {
+            TempPath(std::env::temp_dir().join(format!(
+                "in1weekend-{}-{}.checkpoint",
+                name,
+                std::process::id()
+            )))
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn checkpoint_round_trips() {
+        let path = TempPath::new("round-trip");
+        let accumulator = accumulator();
+        camera().save_checkpoint(&accumulator, &path.0).unwrap();
+
+        let read = camera().load_checkpoint(&path.0).unwrap();
+        assert_eq!((read.width, read.height), (4, 3));
+        for (a, b) in accumulator.pixels.iter().zip(&read.pixels) {
+            assert_eq!(a.sum.red, b.sum.red);
+            assert_eq!(a.samples, b.samples);
+        }
+    }
+
+    #[test]
+    fn checkpoint_of_another_size_is_rejected() {
+        let path = TempPath::new("size");
+        camera().save_checkpoint(&accumulator(), &path.0).unwrap();
+
+        let camera = camera().with_resolution(na::Vector2::new(8, 6));
+        assert!(camera.load_checkpoint(&path.0).is_err());
+    }
+
+    #[test]
+    fn checkpoint_of_another_view_is_rejected() {
+        let path = TempPath::new("view");
+        camera().save_checkpoint(&accumulator(), &path.0).unwrap();
+
+        let fisheye = camera().with_projection(Projection::Fisheye { fov: 180. });
+        assert!(fisheye.load_checkpoint(&path.0).is_err());
+
+        let path = TempPath::new("view-stereo");
+        let stereo = |convergence| {
+            camera().with_stereo(Some(Stereo {
+                interocular: 0.064,
+                convergence: convergence,
+                layout: StereoLayout::TopBottom,
+            }))
+        };
+        stereo(None)
+            .save_checkpoint(&Accumulator::new(4, 6), &path.0)
+            .unwrap();
+        assert!(stereo(None).load_checkpoint(&path.0).is_ok());
+        assert!(stereo(Some(2.)).load_checkpoint(&path.0).is_err());
+    }
+
+    #[test]
+    fn checkpoint_of_another_seed_or_sampler_is_rejected() {
+        let path = TempPath::new("seed");
+        camera().save_checkpoint(&accumulator(), &path.0).unwrap();
+
+        assert!(camera().with_seed(1).load_checkpoint(&path.0).is_err());
+        assert!(camera()
+            .with_sampler(SamplerKind::Sobol)
+            .load_checkpoint(&path.0)
+            .is_err());
+    }
+
+    #[test]
+    fn stratified_checkpoint_only_resumes_with_as_many_samples() {
+        let path = TempPath::new("stratified");
+        let stratified = || camera().with_sampler(SamplerKind::Stratified);
+        stratified()
+            .save_checkpoint(&accumulator(), &path.0)
+            .unwrap();
+
+        assert!(stratified().load_checkpoint(&path.0).is_ok());
+        assert!(stratified()
+            .with_samples(32)
+            .load_checkpoint(&path.0)
+            .is_err());
+
+        // other samplers don't depend on the sample count
+        let path = TempPath::new("independent");
+        camera().save_checkpoint(&accumulator(), &path.0).unwrap();
+        assert!(camera().with_samples(32).load_checkpoint(&path.0).is_ok());
+    }
+
+    #[test]
+    fn anything_else_is_not_a_checkpoint() {
+        let path = TempPath::new("garbage");
+        std::fs::write(&path.0, b"not a checkpoint at all").unwrap();
+
+        assert!(camera().load_checkpoint(&path.0).is_err());
+    }
 }
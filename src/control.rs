@@ -0,0 +1,48 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+// RenderControl:
+//
+// asks a render to stop early, whatever it has accumulated by then is kept
+// it's checked between pixels, so a render stops within a pixel's worth of samples
+// share it behind an Arc to cancel from another thread, e.g. a signal handler
+//
+#[derive(Debug, Default)]
+pub struct RenderControl {
+    cancelled: AtomicBool,
+    time_budget: Option<Duration>,
+    sample_budget: Option<u64>,
+}
+
+impl RenderControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // counted from the start of each render
+    pub fn with_time_budget(mut self, budget: Duration) -> Self {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    // samples taken over the whole image, on top of those a render resumes from
+    pub fn with_sample_budget(mut self, budget: u64) -> Self {
+        self.sample_budget = Some(budget);
+        self
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    // whether a render `elapsed` into its run, having taken `samples`, should stop
+    pub fn should_stop(&self, elapsed: Duration, samples: u64) -> bool {
+        self.is_cancelled()
+            || self.time_budget.map_or(false, |budget| elapsed >= budget)
+            || self.sample_budget.map_or(false, |budget| samples >= budget)
+    }
+}
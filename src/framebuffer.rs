@@ -0,0 +1,514 @@
+use crate::prelude::*;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Write};
+
+// Framebuffer:
+//
+// linear radiance as traced, rows top to bottom
+// nothing is clamped until it's converted to 8-bit sRGB
+//
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Color>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width: width,
+            height: height,
+            pixels: vec![Color::default(); (width * height) as usize],
+        }
+    }
+
+    pub fn from_fn<F: FnMut(u32, u32) -> Color>(width: u32, height: u32, mut f: F) -> Self {
+        let mut framebuffer = Self::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                framebuffer.pixels[(x + y * width) as usize] = f(x, y);
+            }
+        }
+        framebuffer
+    }
+
+    // .hdr files are read as radiance, anything else goes through `image`,
+    // 8-bit files are sRGB encoded unless `linear` is set, e.g. for data like roughness
+    pub fn open<P: AsRef<std::path::Path>>(path: P, linear: bool) -> Result<Self, failure::Error> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        if extension == "hdr" {
+            let reader = std::io::BufReader::new(std::fs::File::open(path)?);
+            let decoder = image::hdr::HDRDecoder::new(reader)?;
+            let metadata = decoder.metadata();
+            let pixels = decoder.read_image_hdr()?;
+
+            return Ok(Self {
+                width: metadata.width,
+                height: metadata.height,
+                pixels: pixels
+                    .into_iter()
+                    .map(|p| Color::new(p.data[0], p.data[1], p.data[2]))
+                    .collect(),
+            });
+        }
+
+        let image = image::open(path)?.to_rgb();
+        Ok(Self::from_fn(image.width(), image.height(), |x, y| {
+            let p = image.get_pixel(x, y).data;
+            let srgb = palette::Srgb::new(p[0], p[1], p[2]).into_format::<Scalar>();
+            if linear {
+                Color::new(srgb.red, srgb.green, srgb.blue)
+            } else {
+                srgb.into_linear()
+            }
+        }))
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Color {
+        self.pixels[(x + y * self.width) as usize]
+    }
+
+    // v goes up from the bottom row, `wrap` is horizontal then vertical
+    pub fn lookup(&self, u: Scalar, v: Scalar, filter: Filter, wrap: (Wrap, Wrap)) -> Color {
+        if self.width == 0 || self.height == 0 {
+            return Color::default();
+        }
+
+        let texel =
+            |x: i64, y: i64| self.get(wrap.0.apply(x, self.width), wrap.1.apply(y, self.height));
+        let x = u * self.width as Scalar;
+        let y = (1. - v) * self.height as Scalar;
+
+        match filter {
+            Filter::Nearest => texel(x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                // texel centers sit at half coordinates
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+
+                let top = texel(x0, y0) * (1. - fx) + texel(x0 + 1, y0) * fx;
+                let bottom = texel(x0, y0 + 1) * (1. - fx) + texel(x0 + 1, y0 + 1) * fx;
+                top * (1. - fy) + bottom * fy
+            }
+        }
+    }
+
+    pub fn to_srgb(&self, tone_map: &ToneMap) -> image::RgbImage {
+        use palette::Pixel;
+
+        image::RgbImage::from_fn(self.width, self.height, |x, y| {
+            let color = tone_map.map(self.get(x, y));
+            let srgb: palette::Srgb<u8> = palette::Srgb::from_linear(color).into_format();
+            image::Rgb {
+                data: *srgb.as_raw(),
+            }
+        })
+    }
+
+    // picks the format from the extension, anything that isn't HDR is tone mapped
+    // and goes through `image`
+    pub fn save<P: AsRef<std::path::Path>>(
+        &self,
+        path: P,
+        tone_map: &ToneMap,
+    ) -> Result<(), failure::Error> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        let writer =
+            || -> std::io::Result<_> { Ok(std::io::BufWriter::new(std::fs::File::create(path)?)) };
+
+        match extension.as_str() {
+            "exr" => self.write_exr(&mut writer()?)?,
+            "hdr" => self.write_hdr(&mut writer()?)?,
+            "pfm" => self.write_pfm(&mut writer()?)?,
+            _ => self.to_srgb(tone_map).save(path)?,
+        }
+        Ok(())
+    }
+
+    // single part, scanlines, uncompressed 32-bit float channels
+    pub fn write_exr<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        let mut header = Vec::new();
+        header.write_all(&[0x76, 0x2f, 0x31, 0x01])?;
+        header.write_i32::<LittleEndian>(2)?;
+
+        // channels are stored sorted by name
+        let mut channels = Vec::new();
+        for name in &["B", "G", "R"] {
+            channels.write_all(name.as_bytes())?;
+            channels.write_all(&[0])?;
+            channels.write_i32::<LittleEndian>(2)?; // FLOAT
+            channels.write_all(&[0, 0, 0, 0])?; // pLinear, reserved
+            channels.write_i32::<LittleEndian>(1)?;
+            channels.write_i32::<LittleEndian>(1)?;
+        }
+        channels.write_all(&[0])?;
+
+        let mut one = Vec::new();
+        one.write_f32::<LittleEndian>(1.)?;
+
+        let mut window = Vec::new();
+        for v in &[0, 0, self.width as i32 - 1, self.height as i32 - 1] {
+            window.write_i32::<LittleEndian>(*v)?;
+        }
+
+        let mut attribute = |name: &str, kind: &str, value: &[u8]| -> std::io::Result<()> {
+            header.write_all(name.as_bytes())?;
+            header.write_all(&[0])?;
+            header.write_all(kind.as_bytes())?;
+            header.write_all(&[0])?;
+            header.write_i32::<LittleEndian>(value.len() as i32)?;
+            header.write_all(value)
+        };
+
+        attribute("channels", "chlist", &channels)?;
+        attribute("compression", "compression", &[0])?;
+        attribute("dataWindow", "box2i", &window)?;
+        attribute("displayWindow", "box2i", &window)?;
+        attribute("lineOrder", "lineOrder", &[0])?;
+        attribute("pixelAspectRatio", "float", &one)?;
+        attribute("screenWindowCenter", "v2f", &[0; 8])?;
+        attribute("screenWindowWidth", "float", &one)?;
+        header.write_all(&[0])?;
+
+        let line_size = 8 + self.width as u64 * 3 * 4;
+        let first_line = header.len() as u64 + self.height as u64 * 8;
+
+        w.write_all(&header)?;
+        for y in 0..self.height as u64 {
+            w.write_u64::<LittleEndian>(first_line + y * line_size)?;
+        }
+
+        for y in 0..self.height {
+            w.write_i32::<LittleEndian>(y as i32)?;
+            w.write_i32::<LittleEndian>((line_size - 8) as i32)?;
+            let row = &self.pixels[(y * self.width) as usize..((y + 1) * self.width) as usize];
+            for c in row {
+                w.write_f32::<LittleEndian>(c.blue)?;
+            }
+            for c in row {
+                w.write_f32::<LittleEndian>(c.green)?;
+            }
+            for c in row {
+                w.write_f32::<LittleEndian>(c.red)?;
+            }
+        }
+        w.flush()
+    }
+
+    // Radiance RGBE
+    pub fn write_hdr<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        let pixels: Vec<_> = self
+            .pixels
+            .iter()
+            .map(|c| image::Rgb {
+                data: [c.red, c.green, c.blue],
+            })
+            .collect();
+
+        image::hdr::HDREncoder::new(&mut *w).encode(
+            &pixels,
+            self.width as usize,
+            self.height as usize,
+        )?;
+        w.flush()
+    }
+
+    // Portable Float Map, rows bottom to top, a negative scale means little endian
+    pub fn write_pfm<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        write!(w, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+
+        for y in (0..self.height).rev() {
+            for x in 0..self.width {
+                let c = self.get(x, y);
+                w.write_f32::<LittleEndian>(c.red)?;
+                w.write_f32::<LittleEndian>(c.green)?;
+                w.write_f32::<LittleEndian>(c.blue)?;
+            }
+        }
+        w.flush()
+    }
+}
+
+// Accumulator:
+//
+// running estimates of every pixel, samples can keep being added to it
+//
+pub struct Accumulator {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Estimate>,
+}
+
+impl Accumulator {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width: width,
+            height: height,
+            pixels: vec![Estimate::default(); (width * height) as usize],
+        }
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Estimate {
+        self.pixels[(x + y * self.width) as usize]
+    }
+
+    pub fn get_mut(&mut self, x: u32, y: u32) -> &mut Estimate {
+        &mut self.pixels[(x + y * self.width) as usize]
+    }
+
+    pub fn samples(&self) -> u64 {
+        self.pixels.iter().map(|p| p.samples as u64).sum()
+    }
+
+    pub fn write<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        w.write_u32::<LittleEndian>(self.width)?;
+        w.write_u32::<LittleEndian>(self.height)?;
+        for p in &self.pixels {
+            w.write_f32::<LittleEndian>(p.sum.red)?;
+            w.write_f32::<LittleEndian>(p.sum.green)?;
+            w.write_f32::<LittleEndian>(p.sum.blue)?;
+            w.write_u32::<LittleEndian>(p.samples)?;
+            w.write_f32::<LittleEndian>(p.mean)?;
+            w.write_f32::<LittleEndian>(p.m2)?;
+        }
+        Ok(())
+    }
+
+    // fails on anything but a `width` by `height` accumulator, before allocating for it
+    pub fn read<R: Read>(r: &mut R, width: u32, height: u32) -> std::io::Result<Self> {
+        use std::io::{Error, ErrorKind};
+
+        let (read_width, read_height) =
+            (r.read_u32::<LittleEndian>()?, r.read_u32::<LittleEndian>()?);
+        if read_width != width || read_height != height {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "the accumulator is {}x{}, expected {}x{}",
+                    read_width, read_height, width, height
+                ),
+            ));
+        }
+
+        let count = width
+            .checked_mul(height)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "the accumulator is too large"))?;
+        let mut pixels = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            pixels.push(Estimate {
+                sum: Color::new(
+                    r.read_f32::<LittleEndian>()?,
+                    r.read_f32::<LittleEndian>()?,
+                    r.read_f32::<LittleEndian>()?,
+                ),
+                samples: r.read_u32::<LittleEndian>()?,
+                mean: r.read_f32::<LittleEndian>()?,
+                m2: r.read_f32::<LittleEndian>()?,
+            });
+        }
+
+        Ok(Self {
+            width: width,
+            height: height,
+            pixels: pixels,
+        })
+    }
+
+    pub fn to_framebuffer(&self) -> Framebuffer {
+        Framebuffer {
+            width: self.width,
+            height: self.height,
+            pixels: self.pixels.iter().map(Estimate::color).collect(),
+        }
+    }
+}
+
+// the sum of a pixel's samples, and the variance of their luminance by Welford's algorithm
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Estimate {
+    pub sum: Color,
+    pub samples: u32,
+    pub mean: Scalar,
+    pub m2: Scalar,
+}
+
+impl Estimate {
+    pub fn push(&mut self, sample: Color) {
+        let value = luminance(sample);
+        self.sum = self.sum + sample;
+        self.samples += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.samples as Scalar;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    pub fn color(&self) -> Color {
+        if self.samples == 0 {
+            return Color::default();
+        }
+        self.sum / self.samples as Scalar
+    }
+
+    // of the mean luminance
+    pub fn standard_error(&self) -> Scalar {
+        if self.samples < 2 {
+            return std::f32::INFINITY;
+        }
+        (self.m2 / ((self.samples - 1) as Scalar * self.samples as Scalar)).sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accumulator(width: u32, height: u32) -> Accumulator {
+        let mut accumulator = Accumulator::new(width, height);
+        for (i, estimate) in accumulator.pixels.iter_mut().enumerate() {
+            for sample in 0..=i {
+                estimate.push(Color::new(i as Scalar, sample as Scalar, 0.5));
+            }
+        }
+        accumulator
+    }
+
+    fn framebuffer(width: u32, height: u32) -> Framebuffer {
+        Framebuffer::from_fn(width, height, |x, y| {
+            Color::new(x as Scalar, y as Scalar, (x + y * width) as Scalar / 10.)
+        })
+    }
+
+    fn f32_at(bytes: &[u8], at: usize) -> f32 {
+        (&bytes[at..]).read_f32::<LittleEndian>().unwrap()
+    }
+
+    #[test]
+    fn accumulator_round_trips() {
+        let accumulator = accumulator(3, 2);
+        let mut bytes = Vec::new();
+        accumulator.write(&mut bytes).unwrap();
+
+        let read = Accumulator::read(&mut &bytes[..], 3, 2).unwrap();
+        assert_eq!((read.width, read.height), (3, 2));
+        assert_eq!(read.pixels.len(), accumulator.pixels.len());
+        for (a, b) in accumulator.pixels.iter().zip(&read.pixels) {
+            assert_eq!(
+                (a.sum.red, a.sum.green, a.sum.blue),
+                (b.sum.red, b.sum.green, b.sum.blue)
+            );
+            assert_eq!((a.samples, a.mean, a.m2), (b.samples, b.mean, b.m2));
+        }
+    }
+
+    #[test]
+    fn accumulator_of_another_size_is_rejected() {
+        let mut bytes = Vec::new();
+        accumulator(3, 2).write(&mut bytes).unwrap();
+
+        let error = Accumulator::read(&mut &bytes[..], 2, 3).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn oversized_accumulator_is_rejected_before_allocating() {
+        let mut bytes = Vec::new();
+        bytes.write_u32::<LittleEndian>(std::u32::MAX).unwrap();
+        bytes.write_u32::<LittleEndian>(std::u32::MAX).unwrap();
+
+        let error = Accumulator::read(&mut &bytes[..], std::u32::MAX, std::u32::MAX)
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn truncated_accumulator_is_rejected() {
+        let mut bytes = Vec::new();
+        accumulator(3, 2).write(&mut bytes).unwrap();
+        bytes.truncate(bytes.len() - 1);
+
+        let error = Accumulator::read(&mut &bytes[..], 3, 2).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn pfm_is_written_bottom_to_top() {
+        let framebuffer = framebuffer(2, 3);
+        let mut bytes = Vec::new();
+        framebuffer.write_pfm(&mut bytes).unwrap();
+
+        let header = b"PF\n2 3\n-1.0\n";
+        assert!(bytes.starts_with(header));
+        assert_eq!(bytes.len(), header.len() + 2 * 3 * 3 * 4);
+
+        // the bottom left pixel comes first, the top right one last
+        let first = framebuffer.get(0, 2);
+        assert_eq!(f32_at(&bytes, header.len()), first.red);
+        assert_eq!(f32_at(&bytes, header.len() + 4), first.green);
+        assert_eq!(f32_at(&bytes, header.len() + 8), first.blue);
+        let last = framebuffer.get(1, 0);
+        assert_eq!(f32_at(&bytes, bytes.len() - 4), last.blue);
+    }
+
+    #[test]
+    fn exr_scanlines_follow_the_offset_table() {
+        let (width, height) = (3, 2);
+        let framebuffer = framebuffer(width, height);
+        let mut bytes = Vec::new();
+        framebuffer.write_exr(&mut bytes).unwrap();
+
+        assert_eq!(&bytes[..4], &[0x76, 0x2f, 0x31, 0x01]);
+
+        // the header ends after the last attribute's value and a null byte
+        let last = b"screenWindowWidth\0float\0";
+        let header = bytes
+            .windows(last.len())
+            .position(|window| window == last)
+            .unwrap()
+            + last.len()
+            + 4
+            + 4
+            + 1;
+        assert_eq!(bytes[header - 1], 0);
+
+        let line_size = 8 + width as usize * 3 * 4;
+        assert_eq!(
+            bytes.len(),
+            header + height as usize * 8 + height as usize * line_size
+        );
+
+        for y in 0..height {
+            let offset = (&bytes[header + y as usize * 8..])
+                .read_u64::<LittleEndian>()
+                .unwrap() as usize;
+            let mut line = &bytes[offset..];
+            assert_eq!(line.read_i32::<LittleEndian>().unwrap(), y as i32);
+            assert_eq!(
+                line.read_i32::<LittleEndian>().unwrap() as usize,
+                line_size - 8
+            );
+
+            // channels one after the other, sorted by name
+            for x in 0..width {
+                let c = framebuffer.get(x, y);
+                let at = offset + 8 + x as usize * 4;
+                assert_eq!(f32_at(&bytes, at), c.blue);
+                assert_eq!(f32_at(&bytes, at + width as usize * 4), c.green);
+                assert_eq!(f32_at(&bytes, at + width as usize * 8), c.red);
+            }
+        }
+    }
+}
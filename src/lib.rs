@@ -14,24 +14,51 @@ pub extern crate palette;
 pub extern crate rand;
 pub extern crate rayon;
 
+extern crate byteorder;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate tobj;
+extern crate toml;
+
+pub mod background;
 pub mod camera;
+pub mod control;
+pub mod framebuffer;
 pub mod material;
+pub mod mesh;
 pub mod object;
 pub mod presets;
+pub mod progress;
 pub mod ray;
+pub mod sampler;
 pub mod scene;
 pub mod shape;
+pub mod stats;
 pub mod texture;
+pub mod tile;
+pub mod tonemap;
 
 pub mod prelude {
+    pub use crate::background::*;
     pub use crate::camera::*;
+    pub use crate::control::*;
+    pub use crate::framebuffer::*;
     pub use crate::material::*;
+    pub use crate::mesh::*;
     pub use crate::object::*;
+    pub use crate::progress::*;
     pub use crate::ray::*;
+    pub use crate::sampler::*;
     pub use crate::scene::*;
     pub use crate::shape::*;
+    pub use crate::stats::RenderStats;
     pub use crate::texture::*;
-    pub use crate::{Reflect, SphereRandom};
+    pub use crate::tile::*;
+    pub use crate::tonemap::*;
+    pub use crate::{ConeRandom, OrthonormalBasis, Reflect, SphereRandom};
+    pub use rand::rngs::SmallRng;
+    pub use std::sync::Arc;
 
     // TODO: Generics
     //          scalar for space
@@ -39,10 +66,9 @@ pub mod prelude {
 
     pub type Color = palette::LinSrgb;
     pub type AABB = nc::bounding_volume::AABB<Scalar>;
-    pub type BVT = nc::partitioning::BVT<Box<Object>, AABB>;
+    pub type BVT = nc::partitioning::BVT<Arc<Object>, AABB>;
     pub type Isometry = na::Isometry3<Scalar>;
     pub type Point = na::Point3<Scalar>;
-    pub type Ray = nc::query::Ray<Scalar>;
     pub type RayIntersection = nc::query::RayIntersection<Scalar>;
     pub type RayCast = nc::query::RayCast<Scalar>;
     pub type Scalar = f32;
@@ -67,26 +93,78 @@ impl Reflect for Vector {
 }
 
 pub trait SphereRandom {
-    fn random_in_sphere() -> Self;
-    fn random_on_sphere() -> Self;
+    fn random_in_sphere(sampler: &mut Sampler) -> Self;
+    fn random_on_sphere(sampler: &mut Sampler) -> Self;
 }
 
 impl SphereRandom for Vector {
-    fn random_in_sphere() -> Self {
-        Vector::random_on_sphere() * rand::random::<Scalar>()
+    fn random_in_sphere(sampler: &mut Sampler) -> Self {
+        Vector::random_on_sphere(sampler) * sampler.get_1d()
     }
 
-    fn random_on_sphere() -> Self {
-        (rand::random::<Vector>() * 2. - Vector::new(1., 1., 1.)).normalize()
+    // uniform, light sampling relies on it
+    fn random_on_sphere(sampler: &mut Sampler) -> Self {
+        let u = sampler.get_2d();
+        let z = 1. - 2. * u.x;
+        let r = (1. - z * z).max(0.).sqrt();
+        let phi = 2. * std::f32::consts::PI * u.y;
+        Vector::new(r * phi.cos(), r * phi.sin(), z)
     }
 }
 
 impl SphereRandom for Vector2 {
-    fn random_in_sphere() -> Self {
-        Vector2::random_on_sphere() * rand::random::<Scalar>()
+    // uniform over the disc, by Shirley and Chiu's concentric mapping of the square,
+    // samples spread evenly over the square stay so
+    fn random_in_sphere(sampler: &mut Sampler) -> Self {
+        use std::f32::consts::FRAC_PI_4;
+
+        let u = sampler.get_2d() * 2. - Vector2::new(1., 1.);
+        if u.x == 0. && u.y == 0. {
+            return Vector2::zeros();
+        }
+
+        let (radius, theta) = if u.x.abs() > u.y.abs() {
+            (u.x, FRAC_PI_4 * u.y / u.x)
+        } else {
+            (u.y, 2. * FRAC_PI_4 - FRAC_PI_4 * u.x / u.y)
+        };
+        Vector2::new(radius * theta.cos(), radius * theta.sin())
+    }
+
+    fn random_on_sphere(sampler: &mut Sampler) -> Self {
+        let phi = 2. * std::f32::consts::PI * sampler.get_1d();
+        Vector2::new(phi.cos(), phi.sin())
+    }
+}
+
+pub trait ConeRandom {
+    fn random_in_cone(&self, cos_max: Scalar, sampler: &mut Sampler) -> Self;
+}
+
+impl ConeRandom for Vector {
+    // uniform by solid angle around self, which must be normalized
+    fn random_in_cone(&self, cos_max: Scalar, sampler: &mut Sampler) -> Self {
+        let (u, v) = self.orthonormal_basis();
+        let sample = sampler.get_2d();
+        let cosine = 1. - sample.x * (1. - cos_max);
+        let sine = (1. - cosine * cosine).max(0.).sqrt();
+        let phi = 2. * std::f32::consts::PI * sample.y;
+        u * (sine * phi.cos()) + v * (sine * phi.sin()) + self * cosine
     }
+}
+
+pub trait OrthonormalBasis {
+    fn orthonormal_basis(&self) -> (Vector, Vector);
+}
 
-    fn random_on_sphere() -> Self {
-        (rand::random::<Vector2>() * 2. - Vector2::new(1., 1.)).normalize()
+impl OrthonormalBasis for Vector {
+    fn orthonormal_basis(&self) -> (Vector, Vector) {
+        let other = if self.x.abs() > 0.9 {
+            Vector::y()
+        } else {
+            Vector::x()
+        };
+        let u = self.cross(&other).normalize();
+        (u, self.cross(&u))
     }
 }
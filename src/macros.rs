@@ -17,10 +17,10 @@ macro_rules! mkScene {
         background: [ $( $color:expr ),* ],
         objects: [ $( $object:tt ),* ],
     } => {
-        Scene {
-            background: palette::gradient::Gradient::new(vec![ $( $color ),* ]),
-            objects: BVT::new_balanced(vec![ $( mkObject!($object) ),* ]),
-        }
+        Scene::new(
+            palette::gradient::Gradient::new(vec![ $( $color ),* ]),
+            vec![ $( mkObject!($object) ),* ],
+        )
     };
 }
 
@@ -74,18 +74,46 @@ macro_rules! mkObject {
         })
     };
 
+    {{
+        shape: $shape:expr,
+        material: $material:expr,
+        translation: $translation:expr,
+        translation_end: $translation_end:expr,
+    }} => {
+        mkObject!({
+            shape: $shape,
+            material: $material,
+            transform: Isometry::new($translation, Vector::zeros()),
+            transform_end: Isometry::new($translation_end, Vector::zeros()),
+        })
+    };
+
     {{
         shape: $shape:expr,
         material: $material:expr,
         transform: $transform:expr,
     }} => {
-        (
-            Box::new(crate::object::ObjectInner {
-                shape: $shape,
-                material: $material,
-                transform: $transform,
-            }),
-            $shape.aabb(&$transform),
-        )
+        mkObject!({
+            shape: $shape,
+            material: $material,
+            transform: $transform,
+            transform_end: $transform,
+        })
     };
+
+    {{
+        shape: $shape:expr,
+        material: $material:expr,
+        transform: $transform:expr,
+        transform_end: $transform_end:expr,
+    }} => {{
+        let object = std::sync::Arc::new(crate::object::ObjectInner {
+            shape: $shape,
+            material: $material,
+            transform: $transform,
+            transform_end: $transform_end,
+        });
+        let aabb = crate::object::Object::aabb(&*object);
+        (object as std::sync::Arc<crate::object::Object>, aabb)
+    }};
 }
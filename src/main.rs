@@ -1,41 +1,540 @@
 extern crate clap;
+extern crate ctrlc;
 extern crate in1weekend;
 
+use in1weekend::na;
+use in1weekend::prelude::*;
 use in1weekend::presets::*;
 
 fn main() -> Result<(), failure::Error> {
     let matches = clap::App::new("in1weekend")
         .arg(
             clap::Arg::with_name("PRESET")
+                .help("preset name or path to a .toml scene file")
                 .multiple(true)
                 .default_value("cornell"),
         )
+        .arg(
+            clap::Arg::with_name("width")
+                .long("width")
+                .takes_value(true)
+                .help("overrides the width, keeps the aspect ratio unless --height is given"),
+        )
+        .arg(
+            clap::Arg::with_name("height")
+                .long("height")
+                .takes_value(true)
+                .help("overrides the height, keeps the aspect ratio unless --width is given"),
+        )
+        .arg(
+            clap::Arg::with_name("samples")
+                .long("samples")
+                .takes_value(true)
+                .help("overrides the samples per pixel"),
+        )
+        .arg(
+            clap::Arg::with_name("seed")
+                .long("seed")
+                .takes_value(true)
+                .help("overrides the seed, renders with the same seed are identical"),
+        )
+        .arg(
+            clap::Arg::with_name("sampler")
+                .long("sampler")
+                .takes_value(true)
+                .possible_values(&["independent", "stratified", "halton", "sobol"])
+                .help("overrides how sample dimensions are picked"),
+        )
+        .arg(
+            clap::Arg::with_name("adaptive")
+                .long("adaptive")
+                .takes_value(true)
+                .help("stops pixels whose relative noise falls below this, --samples is the most they take"),
+        )
+        .arg(
+            clap::Arg::with_name("min-samples")
+                .long("min-samples")
+                .takes_value(true)
+                .default_value("64")
+                .help("samples between noise checks, for --adaptive"),
+        )
+        .arg(
+            clap::Arg::with_name("projection")
+                .long("projection")
+                .takes_value(true)
+                .possible_values(&["orthographic", "equirectangular", "fisheye"])
+                .help("replaces a perspective camera's projection, from the same point of view"),
+        )
+        .arg(
+            clap::Arg::with_name("fisheye-fov")
+                .long("fisheye-fov")
+                .takes_value(true)
+                .default_value("180")
+                .help("degrees across the shorter side, for --projection fisheye"),
+        )
+        .arg(
+            clap::Arg::with_name("aperture-blades")
+                .long("aperture-blades")
+                .takes_value(true)
+                .help("makes the aperture a polygon with this many sides"),
+        )
+        .arg(
+            clap::Arg::with_name("aperture-rotation")
+                .long("aperture-rotation")
+                .takes_value(true)
+                .default_value("0")
+                .help("degrees, for --aperture-blades"),
+        )
+        .arg(
+            clap::Arg::with_name("aperture-mask")
+                .long("aperture-mask")
+                .takes_value(true)
+                .conflicts_with("aperture-blades")
+                .help("image the aperture takes the shape of, light goes through its bright parts"),
+        )
+        .arg(
+            clap::Arg::with_name("cat-eye")
+                .long("cat-eye")
+                .takes_value(true)
+                .help("how much the lens barrel cuts into out of focus highlights toward the edges"),
+        )
+        .arg(
+            clap::Arg::with_name("stereo")
+                .long("stereo")
+                .takes_value(true)
+                .possible_values(&["side-by-side", "top-bottom"])
+                .help("renders both eyes, left first, the resolution is that of each eye"),
+        )
+        .arg(
+            clap::Arg::with_name("interocular")
+                .long("interocular")
+                .takes_value(true)
+                .default_value("0.064")
+                .help("distance between the eyes, for --stereo"),
+        )
+        .arg(
+            clap::Arg::with_name("convergence")
+                .long("convergence")
+                .takes_value(true)
+                .help("distance at which the eyes' views meet, for --stereo, parallel without it, not orthographic"),
+        )
+        .arg(
+            clap::Arg::with_name("threads")
+                .long("threads")
+                .takes_value(true)
+                .help("render threads, defaults to one per core"),
+        )
+        .arg(
+            clap::Arg::with_name("tile-size")
+                .long("tile-size")
+                .takes_value(true)
+                .default_value("32")
+                .help("width and height of the tiles handed to threads"),
+        )
+        .arg(
+            clap::Arg::with_name("tile-order")
+                .long("tile-order")
+                .takes_value(true)
+                .possible_values(&["scanline", "spiral", "hilbert"])
+                .default_value("spiral")
+                .help("order tiles are rendered in"),
+        )
+        .arg(
+            clap::Arg::with_name("progressive")
+                .long("progressive")
+                .takes_value(true)
+                .help("saves the output after each pass, of this many samples per pixel instead of 16"),
+        )
+        .arg(
+            clap::Arg::with_name("preview-every")
+                .long("preview-every")
+                .takes_value(true)
+                .default_value("0")
+                .help("seconds between saves, for --progressive"),
+        )
+        .arg(
+            clap::Arg::with_name("time-limit")
+                .long("time-limit")
+                .takes_value(true)
+                .help("seconds after which the render stops and keeps what it has, --samples still caps it"),
+        )
+        .arg(
+            clap::Arg::with_name("sample-limit")
+                .long("sample-limit")
+                .takes_value(true)
+                .help("samples over the whole image after which the render stops and keeps what it has"),
+        )
+        .arg(
+            clap::Arg::with_name("checkpoint")
+                .long("checkpoint")
+                .takes_value(true)
+                .help("file the accumulated samples are regularly saved to"),
+        )
+        .arg(
+            clap::Arg::with_name("checkpoint-every")
+                .long("checkpoint-every")
+                .takes_value(true)
+                .default_value("60")
+                .help("seconds between saves, for --checkpoint"),
+        )
+        .arg(
+            clap::Arg::with_name("resume")
+                .long("resume")
+                .requires("checkpoint")
+                .help("carries on from --checkpoint, up to --samples per pixel"),
+        )
+        .arg(
+            clap::Arg::with_name("progress")
+                .long("progress")
+                .takes_value(true)
+                .possible_values(&["terminal", "silent", "json"])
+                .default_value("terminal")
+                .help("how progress is reported, json prints one event per line"),
+        )
+        .arg(
+            clap::Arg::with_name("stats")
+                .long("stats")
+                .takes_value(true)
+                .help("file the render statistics are written to, as JSON"),
+        )
+        .arg(
+            clap::Arg::with_name("output")
+                .long("output")
+                .short("o")
+                .takes_value(true)
+                .help("output file, .exr, .hdr and .pfm keep the full range, defaults to <PRESET>.png"),
+        )
+        .arg(
+            clap::Arg::with_name("tone-map")
+                .long("tone-map")
+                .takes_value(true)
+                .possible_values(&["clamp", "reinhard", "extended-reinhard", "aces", "exposure"])
+                .help("overrides how radiance is squeezed into 8-bit outputs"),
+        )
+        .arg(
+            clap::Arg::with_name("exposure")
+                .long("exposure")
+                .takes_value(true)
+                .default_value("0")
+                .help("stops, for --tone-map exposure"),
+        )
+        .arg(
+            clap::Arg::with_name("gamma")
+                .long("gamma")
+                .takes_value(true)
+                .default_value("1")
+                .help("on top of the sRGB curve, for --tone-map exposure"),
+        )
+        .arg(
+            clap::Arg::with_name("white")
+                .long("white")
+                .takes_value(true)
+                .default_value("4")
+                .help("luminance mapped to white, for --tone-map extended-reinhard"),
+        )
+        .arg(
+            clap::Arg::with_name("no-view")
+                .long("no-view")
+                .help("doesn't open the result in feh"),
+        )
         .get_matches();
 
-    for preset in matches.values_of("PRESET").expect("no preset") {
+    let presets: Vec<&str> = matches.values_of("PRESET").expect("no preset").collect();
+
+    for single in &["output", "checkpoint", "stats"] {
+        if presets.len() > 1 && matches.is_present(single) {
+            return Err(failure::format_err!(
+                "--{} can only be used with a single preset",
+                single
+            ));
+        }
+    }
+
+    // the first ctrl-c stops renders where they are, what they have is still saved
+    let control = {
+        let mut control = RenderControl::new();
+        if let Some(seconds) = matches.value_of("time-limit") {
+            let seconds: f64 = seconds.parse()?;
+            control = control
+                .with_time_budget(std::time::Duration::from_millis((seconds * 1000.) as u64));
+        }
+        if let Some(samples) = matches.value_of("sample-limit") {
+            control = control.with_sample_budget(samples.parse()?);
+        }
+        Arc::new(control)
+    };
+    {
+        let control = control.clone();
+        ctrlc::set_handler(move || {
+            if control.is_cancelled() {
+                std::process::exit(130);
+            }
+            control.cancel();
+        })?;
+    }
+
+    for preset in presets {
         match preset {
-            "cornell" => preset!(cornell),
-            "cover1" => preset!(cover1),
-            "cover2" => preset!(cover2),
-            // "presentation" => preset!(presentation),
+            "cornell" => preset!(cornell, matches, control),
+            "cover1" => preset!(cover1, matches, control),
+            "cover2" => preset!(cover2, matches, control),
+            // "presentation" => preset!(presentation, matches, control),
+            path if path.ends_with(".toml") => {
+                let (scene, camera) = in1weekend::scene::load(path)?;
+                let name = std::path::Path::new(path)
+                    .file_stem()
+                    .expect("no file name")
+                    .to_string_lossy();
+
+                render(&name, &scene, camera, &matches, &control)?;
+            }
             _ => {
                 println!("preset not found: {:?}", preset);
             }
         }
+
+        if control.is_cancelled() {
+            break;
+        }
     }
 
     Ok(())
 }
 
-#[macro_export]
-macro_rules! preset {
-    ( $preset:ident ) => {{
-        $preset::camera()
-            .capture(&$preset::scene())
-            .save(concat!(stringify!($preset), ".png"))?;
+fn render(
+    name: &str,
+    scene: &Scene,
+    camera: Camera,
+    matches: &clap::ArgMatches,
+    control: &RenderControl,
+) -> Result<(), failure::Error> {
+    let camera = apply_overrides(camera, matches)?;
+    let output = match matches.value_of("output") {
+        Some(output) => output.to_owned(),
+        None => format!("{}.png", name),
+    };
+
+    let seconds = |name: &str| -> Result<std::time::Duration, failure::Error> {
+        let seconds: f64 = matches.value_of(name).unwrap_or("0").parse()?;
+        Ok(std::time::Duration::from_millis((seconds * 1000.) as u64))
+    };
+    let preview_every = seconds("preview-every")?;
+    let checkpoint_every = seconds("checkpoint-every")?;
+    let checkpoint = matches.value_of("checkpoint");
 
+    let mut accumulator = match checkpoint {
+        Some(path) if matches.is_present("resume") => camera.load_checkpoint(path)?,
+        _ => Accumulator::new(camera.image_size().x, camera.image_size().y),
+    };
+
+    // checkpoints only happen between passes, keep them short enough to save regularly,
+    // and a render cut short, by a limit or Ctrl-C, in one long pass would leave part
+    // of the image black
+    let pass_samples = match matches.value_of("progressive") {
+        Some(pass_samples) => pass_samples.parse()?,
+        None => camera.samples().min(16),
+    };
+
+    let mut previewed = std::time::Instant::now();
+    let mut checkpointed = std::time::Instant::now();
+    let progress: Box<ProgressSink> = match matches.value_of("progress") {
+        Some("silent") => Box::new(SilentProgress),
+        Some("json") => Box::new(JsonProgress),
+        _ => Box::new(TerminalProgress::new()),
+    };
+
+    let stats = camera.accumulate(
+        scene,
+        &mut accumulator,
+        pass_samples,
+        &*progress,
+        control,
+        |accumulator| {
+            if matches.is_present("progressive") && previewed.elapsed() >= preview_every {
+                let preview = accumulator.to_framebuffer();
+                if let Err(error) = preview.save(&output, camera.tone_map()) {
+                    eprintln!("couldn't save the preview: {}", error);
+                }
+                previewed = std::time::Instant::now();
+            }
+
+            if let Some(path) = checkpoint {
+                if checkpointed.elapsed() >= checkpoint_every {
+                    if let Err(error) = camera.save_checkpoint(accumulator, path) {
+                        eprintln!("couldn't save the checkpoint: {}", error);
+                    }
+                    checkpointed = std::time::Instant::now();
+                }
+            }
+        },
+    );
+
+    // kept once finished, so a later --resume can raise the sample count
+    if let Some(path) = checkpoint {
+        camera.save_checkpoint(&accumulator, path)?;
+    }
+
+    if let Some(path) = matches.value_of("stats") {
+        stats.write_json(&mut std::fs::File::create(path)?)?;
+    }
+
+    let framebuffer = accumulator.to_framebuffer();
+    framebuffer.save(&output, camera.tone_map())?;
+
+    if !matches.is_present("no-view") {
         std::process::Command::new("feh")
-            .args(&["-F", concat!(stringify!($preset), ".png")])
+            .args(&["-F", &output])
             .status()?;
+    }
+
+    Ok(())
+}
+
+fn apply_overrides(
+    mut camera: Camera,
+    matches: &clap::ArgMatches,
+) -> Result<Camera, failure::Error> {
+    let resolution = camera.resolution();
+    let parse = |name: &str| -> Result<Option<u32>, failure::Error> {
+        match matches.value_of(name) {
+            Some(value) => Ok(Some(value.parse()?)),
+            None => Ok(None),
+        }
+    };
+    let scaled =
+        |size: u32, from: u32, to: u32| (size as f32 * to as f32 / from as f32).round() as u32;
+
+    let resolution = match (parse("width")?, parse("height")?) {
+        (Some(width), Some(height)) => na::Vector2::new(width, height),
+        (Some(width), None) => na::Vector2::new(width, scaled(resolution.y, resolution.x, width)),
+        (None, Some(height)) => {
+            na::Vector2::new(scaled(resolution.x, resolution.y, height), height)
+        }
+        (None, None) => resolution,
+    };
+    if resolution.x == 0 || resolution.y == 0 {
+        return Err(failure::format_err!("resolution must not be zero"));
+    }
+    camera = camera.with_resolution(resolution);
+
+    if let Some(samples) = parse("samples")? {
+        camera = camera.with_samples(samples);
+    }
+
+    if let Some(seed) = matches.value_of("seed") {
+        camera = camera.with_seed(seed.parse()?);
+    }
+
+    if let Some(threshold) = matches.value_of("adaptive") {
+        camera = camera.with_adaptive(Some(Adaptive {
+            min_samples: matches.value_of("min-samples").unwrap_or("64").parse()?,
+            threshold: threshold.parse()?,
+        }));
+    }
+
+    match matches.value_of("sampler") {
+        Some("independent") => camera = camera.with_sampler(SamplerKind::Independent),
+        Some("stratified") => camera = camera.with_sampler(SamplerKind::Stratified),
+        Some("halton") => camera = camera.with_sampler(SamplerKind::Halton),
+        Some("sobol") => camera = camera.with_sampler(SamplerKind::Sobol),
+        _ => {}
+    }
+
+    match matches.value_of("projection") {
+        Some("orthographic") => camera = camera.with_projection(Projection::Orthographic),
+        Some("equirectangular") => camera = camera.with_projection(Projection::Equirectangular),
+        Some("fisheye") => {
+            camera = camera.with_projection(Projection::Fisheye {
+                fov: matches.value_of("fisheye-fov").unwrap_or("180").parse()?,
+            })
+        }
+        _ => {}
+    }
+
+    if let Some(blades) = parse("aperture-blades")? {
+        camera = camera.with_aperture_shape(ApertureShape::Polygon {
+            blades: blades,
+            rotation: matches
+                .value_of("aperture-rotation")
+                .unwrap_or("0")
+                .parse()?,
+        });
+    }
+    if let Some(path) = matches.value_of("aperture-mask") {
+        let mask = ApertureMask::new(&Framebuffer::open(path, true)?);
+        camera = camera.with_aperture_shape(ApertureShape::Mask(Arc::new(mask)));
+    }
+    if let Some(strength) = matches.value_of("cat-eye") {
+        camera = camera.with_cat_eye(strength.parse()?);
+    }
+
+    let layout = match matches.value_of("stereo") {
+        Some("side-by-side") => Some(StereoLayout::SideBySide),
+        Some("top-bottom") => Some(StereoLayout::TopBottom),
+        _ => None,
+    };
+    if let Some(layout) = layout {
+        let convergence: Option<Scalar> = match matches.value_of("convergence") {
+            Some(distance) => Some(distance.parse()?),
+            None => None,
+        };
+        if convergence.map_or(false, |convergence| !(convergence > 0.)) {
+            return Err(failure::format_err!("convergence must be positive"));
+        }
+        camera = camera.with_stereo(Some(Stereo {
+            interocular: matches.value_of("interocular").unwrap_or("0.064").parse()?,
+            convergence: convergence,
+            layout: layout,
+        }));
+    }
+    // whether it comes from here or the scene, parallel rays never meet
+    let converging = camera
+        .stereo()
+        .map_or(false, |stereo| stereo.convergence.is_some());
+    if converging && camera.projection() == Projection::Orthographic {
+        return Err(failure::format_err!("orthographic stereo can't converge"));
+    }
+
+    let tile_order = match matches.value_of("tile-order") {
+        Some("scanline") => TileOrder::Scanline,
+        Some("hilbert") => TileOrder::Hilbert,
+        _ => TileOrder::Spiral,
+    };
+    camera = camera.with_tiles(parse("tile-size")?.unwrap_or(32), tile_order);
+
+    if let Some(threads) = parse("threads")? {
+        camera = camera.with_threads(threads as usize);
+    }
+
+    let tone_map: Option<Box<ToneMap>> = match matches.value_of("tone-map") {
+        Some("clamp") => Some(Box::new(Clamp)),
+        Some("reinhard") => Some(Box::new(Reinhard)),
+        Some("extended-reinhard") => Some(Box::new(ExtendedReinhard {
+            white: matches.value_of("white").unwrap_or("4").parse()?,
+        })),
+        Some("aces") => Some(Box::new(AcesFilmic)),
+        Some("exposure") => Some(Box::new(Exposure {
+            stops: matches.value_of("exposure").unwrap_or("0").parse()?,
+            gamma: matches.value_of("gamma").unwrap_or("1").parse()?,
+        })),
+        _ => None,
+    };
+    if let Some(tone_map) = tone_map {
+        camera = camera.with_tone_map(tone_map);
+    }
+
+    Ok(camera)
+}
+
+#[macro_export]
+macro_rules! preset {
+    ( $preset:ident, $matches:expr, $control:expr ) => {{
+        render(
+            stringify!($preset),
+            &$preset::scene(),
+            $preset::camera(),
+            &$matches,
+            &$control,
+        )?;
     }};
 }
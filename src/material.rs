@@ -1,13 +1,128 @@
 use crate::prelude::*;
 
+// Material:
+//
+// `ray` and `intersection` locate the surface, textures are sampled from them
+// `wo` points back along the incoming ray, `wi` toward where light arrives from,
+// both normalized, scattered light flows from `wi` to `wo`
+//
 pub trait Material {
-    fn scatter(&self, _ray: &Ray, _intersection: &RayIntersection) -> Option<(Ray, Color)> {
+    // light scattered toward `wo` per unit of light arriving from `wi`, cosine included
+    fn eval(
+        &self,
+        _ray: &Ray,
+        _intersection: &RayIntersection,
+        _wo: &Vector,
+        _wi: &Vector,
+    ) -> Color {
+        Color::default()
+    }
+
+    fn sample(
+        &self,
+        _ray: &Ray,
+        _intersection: &RayIntersection,
+        _wo: &Vector,
+        _sampler: &mut Sampler,
+    ) -> Option<BsdfSample> {
         None
     }
 
+    // probability density by solid angle of sample picking `wi`, 0 for specular lobes
+    fn pdf(
+        &self,
+        _ray: &Ray,
+        _intersection: &RayIntersection,
+        _wo: &Vector,
+        _wi: &Vector,
+    ) -> Scalar {
+        0.
+    }
+
+    fn lobes(&self) -> Lobe {
+        Lobe::NONE
+    }
+
     fn emitted(&self, _ray: &Ray, _intersection: &RayIntersection) -> Color {
         Color::default()
     }
+
+    fn emits(&self) -> bool {
+        false
+    }
+}
+
+impl<M: Material + ?Sized> Material for Box<M> {
+    fn eval(&self, ray: &Ray, intersection: &RayIntersection, wo: &Vector, wi: &Vector) -> Color {
+        (**self).eval(ray, intersection, wo, wi)
+    }
+
+    fn sample(
+        &self,
+        ray: &Ray,
+        intersection: &RayIntersection,
+        wo: &Vector,
+        sampler: &mut Sampler,
+    ) -> Option<BsdfSample> {
+        (**self).sample(ray, intersection, wo, sampler)
+    }
+
+    fn pdf(&self, ray: &Ray, intersection: &RayIntersection, wo: &Vector, wi: &Vector) -> Scalar {
+        (**self).pdf(ray, intersection, wo, wi)
+    }
+
+    fn lobes(&self) -> Lobe {
+        (**self).lobes()
+    }
+
+    fn emitted(&self, ray: &Ray, intersection: &RayIntersection) -> Color {
+        (**self).emitted(ray, intersection)
+    }
+
+    fn emits(&self) -> bool {
+        (**self).emits()
+    }
+}
+
+pub struct BsdfSample {
+    pub wi: Vector,
+    // eval / pdf, or the attenuation of a specular lobe
+    pub weight: Color,
+    pub pdf: Scalar,
+    pub lobe: Lobe,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Lobe(u8);
+
+impl Lobe {
+    pub const NONE: Lobe = Lobe(0);
+    pub const DIFFUSE: Lobe = Lobe(1 << 0);
+    pub const GLOSSY: Lobe = Lobe(1 << 1);
+    pub const SPECULAR: Lobe = Lobe(1 << 2);
+    pub const REFLECTION: Lobe = Lobe(1 << 3);
+    pub const TRANSMISSION: Lobe = Lobe(1 << 4);
+
+    pub fn contains(self, other: Lobe) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn intersects(self, other: Lobe) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    // whether eval and pdf are worth calling for this material
+    pub fn is_smooth(self) -> bool {
+        self.intersects(Lobe::DIFFUSE | Lobe::GLOSSY)
+    }
+}
+
+impl std::ops::BitOr for Lobe {
+    type Output = Lobe;
+
+    fn bitor(self, other: Lobe) -> Lobe {
+        Lobe(self.0 | other.0)
+    }
 }
 
 pub struct Lambertian<T: Texture> {
@@ -15,17 +130,36 @@ pub struct Lambertian<T: Texture> {
 }
 
 impl<T: Texture> Material for Lambertian<T> {
-    fn scatter(&self, ray: &Ray, intersection: &RayIntersection) -> Option<(Ray, Color)> {
-        let target = intersection.point(&ray) + intersection.normal + Vector::random_in_sphere();
-        let origin = intersection.point_nudged_out(&ray);
+    fn eval(&self, ray: &Ray, intersection: &RayIntersection, wo: &Vector, wi: &Vector) -> Color {
+        self.albedo.sample(&ray, &intersection) * self.pdf(ray, intersection, wo, wi)
+    }
+
+    fn sample(
+        &self,
+        ray: &Ray,
+        intersection: &RayIntersection,
+        _wo: &Vector,
+        sampler: &mut Sampler,
+    ) -> Option<BsdfSample> {
+        // a point on the unit sphere tangent to the surface gives a cosine distribution
+        let wi = (intersection.normal + Vector::random_on_sphere(sampler))
+            .try_normalize(1e-6)
+            .unwrap_or(intersection.normal);
+
+        Some(BsdfSample {
+            wi: wi,
+            weight: self.albedo.sample(&ray, &intersection),
+            pdf: wi.dot(&intersection.normal).max(0.) / std::f32::consts::PI,
+            lobe: Lobe::DIFFUSE | Lobe::REFLECTION,
+        })
+    }
+
+    fn pdf(&self, _ray: &Ray, intersection: &RayIntersection, _wo: &Vector, wi: &Vector) -> Scalar {
+        wi.dot(&intersection.normal).max(0.) / std::f32::consts::PI
+    }
 
-        Some((
-            Ray {
-                origin: origin,
-                dir: target - origin,
-            },
-            self.albedo.sample(&ray, &intersection),
-        ))
+    fn lobes(&self) -> Lobe {
+        Lobe::DIFFUSE | Lobe::REFLECTION
     }
 }
 
@@ -34,21 +168,75 @@ pub struct Metal<T: Texture> {
     pub fuzz: Scalar,
 }
 
+impl<T: Texture> Metal<T> {
+    // fuzz picks directions uniformly in a cone around the reflection,
+    // as wide as a sphere of radius fuzz seen from the tip of the reflected vector
+    fn cos_max(&self) -> Scalar {
+        if self.fuzz < 1. {
+            (1. - self.fuzz * self.fuzz).sqrt()
+        } else {
+            -1.
+        }
+    }
+}
+
 impl<T: Texture> Material for Metal<T> {
-    fn scatter(&self, ray: &Ray, intersection: &RayIntersection) -> Option<(Ray, Color)> {
-        let reflected = ray.dir.normalize().reflect(&intersection.normal);
+    fn eval(&self, ray: &Ray, intersection: &RayIntersection, wo: &Vector, wi: &Vector) -> Color {
+        self.albedo.sample(&ray, &intersection) * self.pdf(ray, intersection, wo, wi)
+    }
+
+    fn sample(
+        &self,
+        ray: &Ray,
+        intersection: &RayIntersection,
+        wo: &Vector,
+        sampler: &mut Sampler,
+    ) -> Option<BsdfSample> {
+        let reflected = (-wo).reflect(&intersection.normal);
 
         if reflected.dot(&intersection.normal) <= 0. {
             return None;
         }
 
-        Some((
-            Ray {
-                origin: intersection.point_nudged_out(&ray),
-                dir: reflected + self.fuzz * Vector::random_in_sphere(),
-            },
-            self.albedo.sample(&ray, &intersection),
-        ))
+        if self.fuzz <= 0. {
+            return Some(BsdfSample {
+                wi: reflected,
+                weight: self.albedo.sample(&ray, &intersection),
+                pdf: 0.,
+                lobe: Lobe::SPECULAR | Lobe::REFLECTION,
+            });
+        }
+
+        let wi = reflected.random_in_cone(self.cos_max(), sampler);
+
+        if wi.dot(&intersection.normal) <= 0. {
+            return None;
+        }
+
+        Some(BsdfSample {
+            wi: wi,
+            weight: self.albedo.sample(&ray, &intersection),
+            pdf: self.pdf(ray, intersection, wo, &wi),
+            lobe: Lobe::GLOSSY | Lobe::REFLECTION,
+        })
+    }
+
+    fn pdf(&self, _ray: &Ray, intersection: &RayIntersection, wo: &Vector, wi: &Vector) -> Scalar {
+        let reflected = (-wo).reflect(&intersection.normal);
+        let cos_max = self.cos_max();
+
+        if self.fuzz <= 0. || wi.dot(&intersection.normal) <= 0. || wi.dot(&reflected) < cos_max {
+            return 0.;
+        }
+        1. / (2. * std::f32::consts::PI * (1. - cos_max))
+    }
+
+    fn lobes(&self) -> Lobe {
+        if self.fuzz > 0. {
+            Lobe::GLOSSY | Lobe::REFLECTION
+        } else {
+            Lobe::SPECULAR | Lobe::REFLECTION
+        }
     }
 }
 
@@ -58,37 +246,48 @@ pub struct Dielectric<T: Texture> {
 }
 
 impl<T: Texture> Material for Dielectric<T> {
-    fn scatter(&self, ray: &Ray, intersection: &RayIntersection) -> Option<(Ray, Color)> {
-        let rdotn = ray.dir.dot(&intersection.normal);
+    fn sample(
+        &self,
+        ray: &Ray,
+        intersection: &RayIntersection,
+        wo: &Vector,
+        sampler: &mut Sampler,
+    ) -> Option<BsdfSample> {
+        let dir = -wo;
+        let rdotn = dir.dot(&intersection.normal);
 
         let (outward_normal, ni_over_nt, cosine) = if rdotn > 0. {
-            let cosine = self.refraction * rdotn / ray.dir.magnitude();
-            (-intersection.normal, self.refraction, cosine)
+            (
+                -intersection.normal,
+                self.refraction,
+                self.refraction * rdotn,
+            )
         } else {
-            let cosine = -rdotn / ray.dir.magnitude();
-            (intersection.normal, 1. / self.refraction, cosine)
+            (intersection.normal, 1. / self.refraction, -rdotn)
         };
 
-        if let Some(refracted) = refract(&ray.dir, &outward_normal, ni_over_nt) {
+        if let Some(refracted) = refract(&dir, &outward_normal, ni_over_nt) {
             let reflect_prob = schlick(cosine, self.refraction);
-            if rand::random::<f32>() > reflect_prob {
-                return Some((
-                    Ray {
-                        origin: intersection.point_nudged_in(&ray),
-                        dir: refracted,
-                    },
-                    self.attenuation.sample(&ray, &intersection),
-                ));
+            if sampler.get_1d() > reflect_prob {
+                return Some(BsdfSample {
+                    wi: refracted.normalize(),
+                    weight: self.attenuation.sample(&ray, &intersection),
+                    pdf: 0.,
+                    lobe: Lobe::SPECULAR | Lobe::TRANSMISSION,
+                });
             }
         }
 
-        Some((
-            Ray {
-                origin: intersection.point_nudged_out(&ray),
-                dir: ray.dir.reflect(&intersection.normal),
-            },
-            self.attenuation.sample(&ray, &intersection),
-        ))
+        Some(BsdfSample {
+            wi: dir.reflect(&intersection.normal),
+            weight: self.attenuation.sample(&ray, &intersection),
+            pdf: 0.,
+            lobe: Lobe::SPECULAR | Lobe::REFLECTION,
+        })
+    }
+
+    fn lobes(&self) -> Lobe {
+        Lobe::SPECULAR | Lobe::REFLECTION | Lobe::TRANSMISSION
     }
 }
 
@@ -100,6 +299,10 @@ impl<T: Texture> Material for DiffuseLight<T> {
     fn emitted(&self, ray: &Ray, intersection: &RayIntersection) -> Color {
         self.value.sample(ray, intersection)
     }
+
+    fn emits(&self) -> bool {
+        true
+    }
 }
 
 pub struct Isotropic<T: Texture> {
@@ -107,14 +310,37 @@ pub struct Isotropic<T: Texture> {
 }
 
 impl<T: Texture> Material for Isotropic<T> {
-    fn scatter(&self, ray: &Ray, intersection: &RayIntersection) -> Option<(Ray, Color)> {
-        Some((
-            Ray {
-                origin: intersection.point_nudged_out(&ray),
-                dir: Vector::random_on_sphere(),
-            },
-            self.albedo.sample(&ray, &intersection),
-        ))
+    fn eval(&self, ray: &Ray, intersection: &RayIntersection, wo: &Vector, wi: &Vector) -> Color {
+        self.albedo.sample(&ray, &intersection) * self.pdf(ray, intersection, wo, wi)
+    }
+
+    fn sample(
+        &self,
+        ray: &Ray,
+        intersection: &RayIntersection,
+        _wo: &Vector,
+        sampler: &mut Sampler,
+    ) -> Option<BsdfSample> {
+        Some(BsdfSample {
+            wi: Vector::random_on_sphere(sampler),
+            weight: self.albedo.sample(&ray, &intersection),
+            pdf: 0.25 / std::f32::consts::PI,
+            lobe: Lobe::DIFFUSE | Lobe::REFLECTION | Lobe::TRANSMISSION,
+        })
+    }
+
+    fn pdf(
+        &self,
+        _ray: &Ray,
+        _intersection: &RayIntersection,
+        _wo: &Vector,
+        _wi: &Vector,
+    ) -> Scalar {
+        0.25 / std::f32::consts::PI
+    }
+
+    fn lobes(&self) -> Lobe {
+        Lobe::DIFFUSE | Lobe::REFLECTION | Lobe::TRANSMISSION
     }
 }
 
@@ -0,0 +1,307 @@
+use crate::prelude::*;
+
+// Mesh:
+//
+// triangles indexing shared vertices, with optional per-vertex normals and texture coordinates
+// the mesh keeps its own BVT so the scene's tree only ever sees one leaf for it
+//
+pub struct Mesh {
+    vertices: Vec<Point>,
+    normals: Vec<Vector>,
+    uvs: Vec<na::Point2<Scalar>>,
+    triangles: Vec<[usize; 3]>,
+    tree: nc::partitioning::BVT<usize, AABB>,
+    local_aabb: AABB,
+    // running sum of triangle areas, for sampling
+    areas: Vec<Scalar>,
+    // whether it encloses a volume, open surfaces are seen from both sides
+    closed: bool,
+}
+
+impl Mesh {
+    // `normals` and `uvs` are either empty or as long as `vertices`
+    pub fn new(
+        vertices: Vec<Point>,
+        normals: Vec<Vector>,
+        uvs: Vec<na::Point2<Scalar>>,
+        triangles: Vec<[usize; 3]>,
+    ) -> Self {
+        use nc::bounding_volume::BoundingVolume;
+
+        assert!(normals.is_empty() || normals.len() == vertices.len());
+        assert!(uvs.is_empty() || uvs.len() == vertices.len());
+
+        let leaves: Vec<_> = triangles
+            .iter()
+            .enumerate()
+            .map(|(i, t)| {
+                let aabb = AABB::new(vertices[t[0]], vertices[t[0]])
+                    .merged(&AABB::new(vertices[t[1]], vertices[t[1]]))
+                    .merged(&AABB::new(vertices[t[2]], vertices[t[2]]));
+                (i, aabb)
+            })
+            .collect();
+
+        let local_aabb = leaves
+            .iter()
+            .fold(None, |acc: Option<AABB>, (_, aabb)| match acc {
+                Some(acc) => Some(acc.merged(aabb)),
+                None => Some(aabb.clone()),
+            })
+            .unwrap_or_else(|| AABB::new(Point::origin(), Point::origin()));
+
+        let mut total = 0.;
+        let areas = triangles
+            .iter()
+            .map(|t| {
+                let e1 = vertices[t[1]] - vertices[t[0]];
+                let e2 = vertices[t[2]] - vertices[t[0]];
+                total += e1.cross(&e2).magnitude() / 2.;
+                total
+            })
+            .collect();
+
+        Self {
+            closed: is_closed(&vertices, &triangles),
+            vertices: vertices,
+            normals: normals,
+            uvs: uvs,
+            triangles: triangles,
+            tree: nc::partitioning::BVT::new_balanced(leaves),
+            local_aabb: local_aabb,
+            areas: areas,
+        }
+    }
+
+    // every object in the file ends up in the same mesh, materials are ignored
+    pub fn load_obj<P: AsRef<std::path::Path>>(path: P) -> Result<Self, failure::Error> {
+        let (models, _) = tobj::load_obj(path.as_ref())?;
+
+        let mut vertices = Vec::new();
+        let mut normals = Vec::new();
+        let mut uvs = Vec::new();
+        let mut triangles = Vec::new();
+        let mut complete = true;
+
+        for model in models {
+            let mesh = model.mesh;
+            let offset = vertices.len();
+            let count = mesh.positions.len() / 3;
+
+            vertices.extend(
+                mesh.positions
+                    .chunks(3)
+                    .map(|p| Point::new(p[0], p[1], p[2])),
+            );
+            triangles.extend(mesh.indices.chunks(3).map(|t| {
+                [
+                    offset + t[0] as usize,
+                    offset + t[1] as usize,
+                    offset + t[2] as usize,
+                ]
+            }));
+
+            // a model without normals or uvs drops them for the whole mesh
+            complete =
+                complete && mesh.normals.len() == count * 3 && mesh.texcoords.len() == count * 2;
+            normals.extend(
+                mesh.normals
+                    .chunks(3)
+                    .map(|n| Vector::new(n[0], n[1], n[2]).normalize()),
+            );
+            uvs.extend(
+                mesh.texcoords
+                    .chunks(2)
+                    .map(|uv| na::Point2::new(uv[0], uv[1])),
+            );
+        }
+
+        if !complete {
+            normals.clear();
+            uvs.clear();
+        }
+
+        Ok(Mesh::new(vertices, normals, uvs, triangles))
+    }
+
+    // Möller-Trumbore, returns the ray parameter and barycentric coordinates of the hit
+    fn intersect(
+        &self,
+        triangle: usize,
+        ray: &nc::query::Ray<Scalar>,
+    ) -> Option<(Scalar, Scalar, Scalar)> {
+        let [a, b, c] = self.triangles[triangle];
+        let e1 = self.vertices[b] - self.vertices[a];
+        let e2 = self.vertices[c] - self.vertices[a];
+
+        let p = ray.dir.cross(&e2);
+        let determinant = e1.dot(&p);
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+
+        let inverse = 1. / determinant;
+        let t = ray.origin - self.vertices[a];
+        let u = t.dot(&p) * inverse;
+        if u < 0. || u > 1. {
+            return None;
+        }
+
+        let q = t.cross(&e1);
+        let v = ray.dir.dot(&q) * inverse;
+        if v < 0. || u + v > 1. {
+            return None;
+        }
+
+        let toi = e2.dot(&q) * inverse;
+        if toi <= 0. {
+            return None;
+        }
+        Some((toi, u, v))
+    }
+
+    // geometric normal follows the winding, the interpolated one is kept on its side
+    fn normal(&self, triangle: usize, u: Scalar, v: Scalar) -> Vector {
+        let [a, b, c] = self.triangles[triangle];
+        let geometric = (self.vertices[b] - self.vertices[a])
+            .cross(&(self.vertices[c] - self.vertices[a]))
+            .normalize();
+
+        if self.normals.is_empty() {
+            return geometric;
+        }
+
+        let shading = (self.normals[a] * (1. - u - v) + self.normals[b] * u + self.normals[c] * v)
+            .normalize();
+        if shading.dot(&geometric) < 0. {
+            -shading
+        } else {
+            shading
+        }
+    }
+
+    fn uv(&self, triangle: usize, u: Scalar, v: Scalar) -> Option<na::Point2<Scalar>> {
+        if self.uvs.is_empty() {
+            return None;
+        }
+
+        let [a, b, c] = self.triangles[triangle];
+        Some(na::Point2::from_coordinates(
+            self.uvs[a].coords * (1. - u - v) + self.uvs[b].coords * u + self.uvs[c].coords * v,
+        ))
+    }
+}
+
+impl nc::shape::Shape<Scalar> for Mesh {
+    // the local box's corners, moved
+    fn aabb(&self, m: &Isometry) -> AABB {
+        let (mins, maxs) = (self.local_aabb.mins(), self.local_aabb.maxs());
+        let corners: Vec<Point> = (0..8)
+            .map(|i| {
+                m * Point::new(
+                    if i & 1 == 0 { mins.x } else { maxs.x },
+                    if i & 2 == 0 { mins.y } else { maxs.y },
+                    if i & 4 == 0 { mins.z } else { maxs.z },
+                )
+            })
+            .collect();
+        let (mins, maxs) = nc::bounding_volume::point_cloud_aabb(&Isometry::identity(), &corners);
+        AABB::new(mins, maxs)
+    }
+}
+
+impl Geometry for Mesh {
+    fn ray_cast(&self, m: &Isometry, ray: &Ray, _sampler: &mut Sampler) -> Option<RayIntersection> {
+        let local_ray = ray.as_nc().inverse_transform_by(m);
+        let (triangle, (toi, u, v)) = self.tree.best_first_search(&mut CostByTriangle {
+            mesh: self,
+            ray: &local_ray,
+        })?;
+
+        // dielectrics tell inside from outside by the normal, which only means something
+        // on closed meshes, open ones face the ray so their back isn't black
+        let mut normal = self.normal(*triangle, u, v);
+        if !self.closed && normal.dot(&local_ray.dir) > 0. {
+            normal = -normal;
+        }
+
+        Some(RayIntersection {
+            toi: toi,
+            normal: m * normal,
+            uvs: self.uv(*triangle, u, v),
+        })
+    }
+
+    fn area(&self) -> Scalar {
+        self.areas.last().cloned().unwrap_or(0.)
+    }
+
+    fn sample_surface(&self, m: &Isometry, sampler: &mut Sampler) -> Option<(Point, Vector)> {
+        let pick = sampler.get_1d() * self.area();
+        let triangle = match self
+            .areas
+            .binary_search_by(|a| a.partial_cmp(&pick).unwrap_or(std::cmp::Ordering::Less))
+        {
+            Ok(i) | Err(i) => i.min(self.triangles.len().checked_sub(1)?),
+        };
+
+        // folding the unit square onto the triangle keeps the distribution uniform
+        let sample = sampler.get_2d();
+        let (mut u, mut v) = (sample.x, sample.y);
+        if u + v > 1. {
+            u = 1. - u;
+            v = 1. - v;
+        }
+
+        let [a, b, c] = self.triangles[triangle];
+        let point = self.vertices[a]
+            + (self.vertices[b] - self.vertices[a]) * u
+            + (self.vertices[c] - self.vertices[a]) * v;
+        Some((m * point, m * self.normal(triangle, u, v)))
+    }
+}
+
+// every edge is shared by exactly two triangles, vertices at the same position count as one
+// since files often split them along seams of normals or texture coordinates
+fn is_closed(vertices: &[Point], triangles: &[[usize; 3]]) -> bool {
+    use std::collections::HashMap;
+
+    let mut positions = HashMap::new();
+    let canonical: Vec<usize> = vertices
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            *positions
+                .entry([p.x.to_bits(), p.y.to_bits(), p.z.to_bits()])
+                .or_insert(i)
+        })
+        .collect();
+
+    let mut edges = HashMap::new();
+    for t in triangles {
+        for i in 0..3 {
+            let (a, b) = (canonical[t[i]], canonical[t[(i + 1) % 3]]);
+            *edges.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+        }
+    }
+    !edges.is_empty() && edges.values().all(|&count| count == 2)
+}
+
+struct CostByTriangle<'a> {
+    mesh: &'a Mesh,
+    ray: &'a nc::query::Ray<Scalar>,
+}
+
+impl<'a> nc::partitioning::BVTCostFn<Scalar, usize, AABB> for CostByTriangle<'a> {
+    type UserData = (Scalar, Scalar, Scalar);
+
+    fn compute_bv_cost(&mut self, bv: &AABB) -> Option<Scalar> {
+        use ncollide3d::query::RayCast;
+
+        bv.toi_with_ray(&Isometry::identity(), self.ray, true)
+    }
+
+    fn compute_b_cost(&mut self, b: &usize) -> Option<(Scalar, Self::UserData)> {
+        self.mesh.intersect(*b, self.ray).map(|hit| (hit.0, hit))
+    }
+}
@@ -8,41 +8,155 @@ use crate::prelude::*;
 //
 pub trait Object: Send + Sync {
     fn aabb(&self) -> AABB;
-    fn ray_cast(&self, ray: &Ray) -> Option<RayIntersection>;
-    fn material_scatter(&self, ray: &Ray, intersection: &RayIntersection) -> Option<(Ray, Color)>;
+    fn ray_cast(&self, ray: &Ray, sampler: &mut Sampler) -> Option<RayIntersection>;
+    fn material_eval(
+        &self,
+        ray: &Ray,
+        intersection: &RayIntersection,
+        wo: &Vector,
+        wi: &Vector,
+    ) -> Color;
+    fn material_sample(
+        &self,
+        ray: &Ray,
+        intersection: &RayIntersection,
+        wo: &Vector,
+        sampler: &mut Sampler,
+    ) -> Option<BsdfSample>;
+    fn material_pdf(
+        &self,
+        ray: &Ray,
+        intersection: &RayIntersection,
+        wo: &Vector,
+        wi: &Vector,
+    ) -> Scalar;
+    fn material_lobes(&self) -> Lobe;
     fn material_emitted(&self, ray: &Ray, intersection: &RayIntersection) -> Color;
+    fn is_light(&self) -> bool;
+    // point, normal and probability density by area
+    fn sample_surface(
+        &self,
+        time: Scalar,
+        sampler: &mut Sampler,
+    ) -> Option<(Point, Vector, Scalar)>;
+    fn surface_pdf(&self) -> Scalar;
 }
 
 pub struct ObjectInner<M, S>
 where
     M: Material + Sync + Send,
-    S: nc::shape::Shape<Scalar>,
+    S: Geometry,
 {
     pub material: M,
     pub shape: S,
+    // the object moves from `transform` when the shutter opens to `transform_end` when it closes,
+    // it stays at `transform` for cameras with no shutter speed
     pub transform: Isometry,
+    pub transform_end: Isometry,
+}
+
+impl<M, S> ObjectInner<M, S>
+where
+    M: Material + Sync + Send,
+    S: Geometry,
+{
+    fn is_moving(&self) -> bool {
+        self.transform != self.transform_end
+    }
+
+    fn transform_at(&self, time: Scalar) -> Isometry {
+        if !self.is_moving() {
+            return self.transform;
+        }
+
+        let t = na::clamp(time, 0., 1.);
+        let translation = self.transform.translation.vector * (1. - t)
+            + self.transform_end.translation.vector * t;
+        let rotation = self
+            .transform
+            .rotation
+            .try_slerp(&self.transform_end.rotation, t, 1e-6)
+            .unwrap_or(self.transform.rotation);
+
+        Isometry::from_parts(na::Translation3::from_vector(translation), rotation)
+    }
 }
 
 impl<M, S> Object for ObjectInner<M, S>
 where
     M: Material + Sync + Send,
-    S: nc::shape::Shape<Scalar>,
+    S: Geometry,
 {
+    // covers the whole motion, rotations are only approximated by a few steps
     fn aabb(&self) -> AABB {
-        self.shape.aabb(&self.transform)
+        use nc::bounding_volume::BoundingVolume;
+
+        let steps = if self.is_moving() { 16 } else { 0 };
+        (1..=steps).fold(self.shape.aabb(&self.transform), |aabb, step| {
+            let time = step as Scalar / steps as Scalar;
+            aabb.merged(&self.shape.aabb(&self.transform_at(time)))
+        })
     }
 
-    fn ray_cast(&self, ray: &Ray) -> Option<RayIntersection> {
+    fn ray_cast(&self, ray: &Ray, sampler: &mut Sampler) -> Option<RayIntersection> {
         self.shape
-            .as_ray_cast()?
-            .toi_and_normal_and_uv_with_ray(&self.transform, ray, false)
+            .ray_cast(&self.transform_at(ray.time), ray, sampler)
     }
 
-    fn material_scatter(&self, ray: &Ray, intersection: &RayIntersection) -> Option<(Ray, Color)> {
-        self.material.scatter(ray, intersection)
+    fn material_eval(
+        &self,
+        ray: &Ray,
+        intersection: &RayIntersection,
+        wo: &Vector,
+        wi: &Vector,
+    ) -> Color {
+        self.material.eval(ray, intersection, wo, wi)
+    }
+
+    fn material_sample(
+        &self,
+        ray: &Ray,
+        intersection: &RayIntersection,
+        wo: &Vector,
+        sampler: &mut Sampler,
+    ) -> Option<BsdfSample> {
+        self.material.sample(ray, intersection, wo, sampler)
+    }
+
+    fn material_pdf(
+        &self,
+        ray: &Ray,
+        intersection: &RayIntersection,
+        wo: &Vector,
+        wi: &Vector,
+    ) -> Scalar {
+        self.material.pdf(ray, intersection, wo, wi)
+    }
+
+    fn material_lobes(&self) -> Lobe {
+        self.material.lobes()
     }
 
     fn material_emitted(&self, ray: &Ray, intersection: &RayIntersection) -> Color {
         self.material.emitted(ray, intersection)
     }
+
+    fn is_light(&self) -> bool {
+        self.material.emits() && self.shape.area() > 0.
+    }
+
+    fn sample_surface(
+        &self,
+        time: Scalar,
+        sampler: &mut Sampler,
+    ) -> Option<(Point, Vector, Scalar)> {
+        let (point, normal) = self
+            .shape
+            .sample_surface(&self.transform_at(time), sampler)?;
+        Some((point, normal, self.surface_pdf()))
+    }
+
+    fn surface_pdf(&self) -> Scalar {
+        1. / self.shape.area()
+    }
 }
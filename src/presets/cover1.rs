@@ -16,9 +16,11 @@ pub fn camera() -> Camera {
 
 pub fn scene() -> Scene {
     use nc::shape::*;
-    use rand::Rng;
+    use rand::{Rng, SeedableRng};
 
-    let mut objects: Vec<(Box<Object>, AABB)> = Vec::new();
+    // the layout is the same from one run to the next
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut objects: Vec<(Arc<Object>, AABB)> = Vec::new();
 
     objects.push(mkObject!({
         shape: Ball::new(10000.),
@@ -62,32 +64,32 @@ pub fn scene() -> Scene {
     for x in -10..10 {
         for z in -20..0 {
             let translation = Vector::new(
-                x as f32 + rand::random::<f32>(),
+                x as f32 + rng.gen::<f32>(),
                 0.2,
-                z as f32 + rand::random::<f32>(),
+                z as f32 + rng.gen::<f32>(),
             );
 
-            if rand::random() {
+            if rng.gen() {
                 objects.push(mkObject!({
                     shape: Ball::new(0.2),
                     material: Lambertian {
                         albedo: Color::new(
-                            rand::thread_rng().gen_range(0., 1.),
-                            rand::thread_rng().gen_range(0., 1.),
-                            rand::thread_rng().gen_range(0., 1.),
+                            rng.gen_range(0., 1.),
+                            rng.gen_range(0., 1.),
+                            rng.gen_range(0., 1.),
                         ),
                     },
                     translation: translation,
                 }));
-            } else if rand::random() {
+            } else if rng.gen() {
                 objects.push(mkObject!({
                     shape: Ball::new(0.2),
                     material: Metal {
-                        fuzz: rand::random(),
+                        fuzz: rng.gen(),
                         albedo: Color::new(
-                            rand::thread_rng().gen_range(0.5, 1.),
-                            rand::thread_rng().gen_range(0.5, 1.),
-                            rand::thread_rng().gen_range(0.5, 1.),
+                            rng.gen_range(0.5, 1.),
+                            rng.gen_range(0.5, 1.),
+                            rng.gen_range(0.5, 1.),
                         ),
                     },
                     translation: translation,
@@ -96,11 +98,11 @@ pub fn scene() -> Scene {
                 objects.push(mkObject!({
                     shape: Ball::new(0.2),
                     material: Dielectric {
-                        refraction: rand::thread_rng().gen_range(1.5, 3.),
+                        refraction: rng.gen_range(1.5, 3.),
                         attenuation: Color::new(
-                            rand::thread_rng().gen_range(0.9, 1.),
-                            rand::thread_rng().gen_range(0.9, 1.),
-                            rand::thread_rng().gen_range(0.9, 1.),
+                            rng.gen_range(0.9, 1.),
+                            rng.gen_range(0.9, 1.),
+                            rng.gen_range(0.9, 1.),
                         ),
                     },
                     translation: translation,
@@ -109,12 +111,12 @@ pub fn scene() -> Scene {
         }
     }
 
-    Scene {
-        background: palette::gradient::Gradient::new(vec![
+    Scene::new(
+        palette::gradient::Gradient::new(vec![
             Color::new(0.4, 0.5, 1.),
             Color::new(1., 1., 1.),
             Color::new(0.4, 0.5, 1.),
         ]),
-        objects: BVT::new_balanced(objects),
-    }
+        objects,
+    )
 }
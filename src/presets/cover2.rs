@@ -16,9 +16,11 @@ pub fn camera() -> Camera {
 
 pub fn scene() -> Scene {
     use nc::shape::*;
-    use rand::Rng;
+    use rand::{Rng, SeedableRng};
 
-    let mut objects: Vec<(Box<Object>, AABB)> = Vec::new();
+    // the layout is the same from one run to the next
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut objects: Vec<(Arc<Object>, AABB)> = Vec::new();
 
     objects.push(mkObject!({
         shape: Cuboid::new(Vector::new(2., 2., 2.)),
@@ -55,7 +57,7 @@ pub fn scene() -> Scene {
             objects.push(mkObject!({
                 shape: Cuboid::new(Vector::new(1., 1., 1.)),
                 material: Lambertian { albedo: Color::new(0.5, 0.8, 0.5) },
-                translation: Vector::new(x as f32, rand::random::<f32>() - 2., z as f32),
+                translation: Vector::new(x as f32, rng.gen::<f32>() - 2., z as f32),
             }));
         }
     }
@@ -65,15 +67,15 @@ pub fn scene() -> Scene {
             shape: Ball::new(0.5),
             material: Lambertian { albedo: Color::new(1.0, 1.0, 1.0) },
             translation: Vector::new(3., 2., -5.) + Vector::new(
-                rand::random::<f32>() * 2.,
-                rand::random::<f32>() * 2.,
-                rand::random::<f32>() * 2.,
+                rng.gen::<f32>() * 2.,
+                rng.gen::<f32>() * 2.,
+                rng.gen::<f32>() * 2.,
             ),
         }));
     }
 
-    Scene {
-        background: palette::gradient::Gradient::new(vec![Color::new(0.01, 0.01, 0.01)]),
-        objects: BVT::new_balanced(objects),
-    }
+    Scene::new(
+        palette::gradient::Gradient::new(vec![Color::new(0.01, 0.01, 0.01)]),
+        objects,
+    )
 }
@@ -0,0 +1,120 @@
+use crate::prelude::*;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+// ProgressSink:
+//
+// told how a render is going, tiles are finished from several threads at once
+// `elapsed` counts from the start of the render
+//
+pub trait ProgressSink: Sync {
+    // `tiles` over all of the passes left to render
+    fn started(&self, _tiles: u64, _passes: u32) {}
+    fn tile_done(&self, _tile: &Tile, _stats: &RenderStats, _elapsed: Duration) {}
+    fn pass_done(&self, _pass: u32, _elapsed: Duration) {}
+    fn finished(&self, _stats: &RenderStats) {}
+}
+
+pub struct SilentProgress;
+
+impl ProgressSink for SilentProgress {}
+
+// a bar on the terminal, and the statistics once it's over
+pub struct TerminalProgress {
+    bar: indicatif::ProgressBar,
+    samples: AtomicUsize,
+}
+
+impl TerminalProgress {
+    pub fn new() -> Self {
+        let bar = indicatif::ProgressBar::new(0);
+        bar.set_style(
+            indicatif::ProgressStyle::default_bar()
+                .template(concat!(
+                    "{spinner} ",
+                    "{wide_bar} ",
+                    "{percent:>3}%, ",
+                    "Elapsed: {elapsed_precise}, ",
+                    "ETA: {eta_precise}, ",
+                    "Samples per second: {msg} "
+                ))
+                .progress_chars("█▇▆▅▄▃▂▁ "),
+        );
+
+        Self {
+            bar: bar,
+            samples: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl ProgressSink for TerminalProgress {
+    fn started(&self, tiles: u64, _passes: u32) {
+        self.bar.set_length(tiles);
+    }
+
+    fn tile_done(&self, _tile: &Tile, stats: &RenderStats, elapsed: Duration) {
+        let samples = stats.camera_rays as usize;
+        let samples = self.samples.fetch_add(samples, Ordering::Relaxed) + samples;
+        self.bar.inc(1);
+        self.bar
+            .set_message(&samples_per_second(samples as u64, elapsed));
+    }
+
+    fn finished(&self, stats: &RenderStats) {
+        self.bar
+            .finish_with_message(&samples_per_second(stats.camera_rays, stats.wall_time));
+        println!("{}", stats);
+    }
+}
+
+// one JSON object per line on stdout, each with an "event" of
+// "started", "tile", "pass" or "finished"
+pub struct JsonProgress;
+
+impl ProgressSink for JsonProgress {
+    fn started(&self, tiles: u64, passes: u32) {
+        println!(
+            "{}",
+            serde_json::json!({ "event": "started", "tiles": tiles, "passes": passes })
+        );
+    }
+
+    fn tile_done(&self, tile: &Tile, stats: &RenderStats, elapsed: Duration) {
+        println!(
+            "{}",
+            serde_json::json!({
+                "event": "tile",
+                "x": tile.x,
+                "y": tile.y,
+                "width": tile.width,
+                "height": tile.height,
+                "samples": stats.camera_rays,
+                "elapsed": crate::stats::seconds(elapsed),
+            })
+        );
+    }
+
+    fn pass_done(&self, pass: u32, elapsed: Duration) {
+        println!(
+            "{}",
+            serde_json::json!({
+                "event": "pass",
+                "pass": pass,
+                "elapsed": crate::stats::seconds(elapsed),
+            })
+        );
+    }
+
+    fn finished(&self, stats: &RenderStats) {
+        println!(
+            "{}",
+            serde_json::json!({ "event": "finished", "stats": stats.to_json() })
+        );
+    }
+}
+
+fn samples_per_second(samples: u64, elapsed: Duration) -> String {
+    crate::stats::si(samples as f64 / crate::stats::seconds(elapsed))
+}
@@ -1,11 +1,27 @@
 use crate::prelude::*;
 
-const ANTI_ACNE: Scalar = 0.001;
+pub const ANTI_ACNE: Scalar = 0.001;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Ray {
+    pub origin: Point,
+    pub dir: Vector,
+    // fraction of the exposure, 0 when the shutter opens and 1 when it closes,
+    // always 0 for cameras with no shutter speed
+    pub time: Scalar,
+}
+
+impl Ray {
+    pub fn as_nc(&self) -> nc::query::Ray<Scalar> {
+        nc::query::Ray::new(self.origin, self.dir)
+    }
+}
 
 pub trait RayIntersectionPoint {
     fn point(&self, ray: &Ray) -> Point;
     fn point_nudged_out(&self, ray: &Ray) -> Point;
     fn point_nudged_in(&self, ray: &Ray) -> Point;
+    fn point_nudged_toward(&self, ray: &Ray, dir: &Vector) -> Point;
 }
 
 impl RayIntersectionPoint for RayIntersection {
@@ -20,22 +36,34 @@ impl RayIntersectionPoint for RayIntersection {
     fn point_nudged_in(&self, ray: &Ray) -> Point {
         self.point(ray) - self.normal * ANTI_ACNE
     }
+
+    fn point_nudged_toward(&self, ray: &Ray, dir: &Vector) -> Point {
+        if dir.dot(&self.normal) < 0. {
+            self.point_nudged_in(ray)
+        } else {
+            self.point_nudged_out(ray)
+        }
+    }
 }
 
 pub struct CostByRayCast<'a> {
     pub ray: &'a Ray,
+    pub sampler: &'a mut Sampler,
+    // bounding volumes tested so far
+    pub visits: u64,
 }
 
-impl<'a> nc::partitioning::BVTCostFn<Scalar, Box<Object>, AABB> for CostByRayCast<'a> {
+impl<'a> nc::partitioning::BVTCostFn<Scalar, Arc<Object>, AABB> for CostByRayCast<'a> {
     type UserData = RayIntersection;
 
     fn compute_bv_cost(&mut self, bv: &AABB) -> Option<Scalar> {
         use ncollide3d::query::RayCast;
 
-        bv.toi_with_ray(&Isometry::identity(), self.ray, true)
+        self.visits += 1;
+        bv.toi_with_ray(&Isometry::identity(), &self.ray.as_nc(), true)
     }
 
-    fn compute_b_cost(&mut self, b: &Box<Object>) -> Option<(Scalar, Self::UserData)> {
-        b.ray_cast(self.ray).map(|i| (i.toi, i))
+    fn compute_b_cost(&mut self, b: &Arc<Object>) -> Option<(Scalar, Self::UserData)> {
+        b.ray_cast(self.ray, self.sampler).map(|i| (i.toi, i))
     }
 }
@@ -0,0 +1,424 @@
+use crate::prelude::*;
+
+// Sampler:
+//
+// hands out the random numbers of one pixel sample, dimension after dimension,
+// low-discrepancy samplers spread the samples of a pixel evenly in each dimension
+// consumers must ask for the same dimensions in the same order for that to pay off
+//
+pub trait Sampler {
+    // restarts at the first dimension of sample `index` of pixel (x, y)
+    fn start(&mut self, x: u32, y: u32, index: u32);
+    fn get_1d(&mut self) -> Scalar;
+    fn get_2d(&mut self) -> Vector2;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SamplerKind {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+impl SamplerKind {
+    // `samples` per pixel, stratification needs to know how many strata to split into
+    pub fn create(self, seed: u64, samples: u32) -> Box<Sampler> {
+        match self {
+            SamplerKind::Independent => Box::new(Independent::new(seed)),
+            SamplerKind::Stratified => Box::new(Stratified::new(seed, samples)),
+            SamplerKind::Halton => Box::new(Halton::new(seed)),
+            SamplerKind::Sobol => Box::new(Sobol::new(seed)),
+        }
+    }
+}
+
+impl Default for SamplerKind {
+    fn default() -> Self {
+        SamplerKind::Independent
+    }
+}
+
+// uniform random numbers, every sample of every pixel gets its own stream
+pub struct Independent {
+    seed: u64,
+    rng: SmallRng,
+}
+
+impl Independent {
+    pub fn new(seed: u64) -> Self {
+        use rand::SeedableRng;
+
+        Self {
+            seed: seed,
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Sampler for Independent {
+    fn start(&mut self, x: u32, y: u32, index: u32) {
+        use rand::SeedableRng;
+
+        self.rng = SmallRng::seed_from_u64(hash(&[self.seed, x as u64, y as u64, index as u64]));
+    }
+
+    fn get_1d(&mut self) -> Scalar {
+        use rand::Rng;
+
+        self.rng.gen()
+    }
+
+    fn get_2d(&mut self) -> Vector2 {
+        use rand::Rng;
+
+        Vector2::new(self.rng.gen(), self.rng.gen())
+    }
+}
+
+// jittered strata, shuffled differently for each pixel and dimension
+// 2D dimensions use a square grid, as close to `samples` cells as possible
+pub struct Stratified {
+    seed: u64,
+    samples: u32,
+    pixel: u64,
+    index: u32,
+    dimension: u64,
+    rng: SmallRng,
+}
+
+impl Stratified {
+    pub fn new(seed: u64, samples: u32) -> Self {
+        use rand::SeedableRng;
+
+        Self {
+            seed: seed,
+            samples: samples.max(1),
+            pixel: 0,
+            index: 0,
+            dimension: 0,
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+
+    fn stratum(&mut self, strata: u32) -> u32 {
+        let shuffle = hash(&[self.pixel, self.dimension]) as u32;
+        self.dimension += 1;
+        permute(self.index % strata, strata, shuffle)
+    }
+}
+
+impl Sampler for Stratified {
+    fn start(&mut self, x: u32, y: u32, index: u32) {
+        use rand::SeedableRng;
+
+        self.pixel = hash(&[self.seed, x as u64, y as u64]);
+        self.index = index;
+        self.dimension = 0;
+        self.rng = SmallRng::seed_from_u64(hash(&[self.pixel, index as u64]));
+    }
+
+    fn get_1d(&mut self) -> Scalar {
+        use rand::Rng;
+
+        let stratum = self.stratum(self.samples);
+        to_unit((stratum as Scalar + self.rng.gen::<Scalar>()) / self.samples as Scalar)
+    }
+
+    fn get_2d(&mut self) -> Vector2 {
+        use rand::Rng;
+
+        let side = (self.samples as Scalar).sqrt().ceil() as u32;
+        let cell = self.stratum(side * side);
+        Vector2::new(
+            to_unit(((cell % side) as Scalar + self.rng.gen::<Scalar>()) / side as Scalar),
+            to_unit(((cell / side) as Scalar + self.rng.gen::<Scalar>()) / side as Scalar),
+        )
+    }
+}
+
+// radical inverses in successive prime bases, randomly shifted per pixel,
+// dimensions past the prime table fall back to independent numbers
+pub struct Halton {
+    seed: u64,
+    pixel: u64,
+    index: u32,
+    dimension: usize,
+    rng: SmallRng,
+}
+
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+impl Halton {
+    pub fn new(seed: u64) -> Self {
+        use rand::SeedableRng;
+
+        Self {
+            seed: seed,
+            pixel: 0,
+            index: 0,
+            dimension: 0,
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Sampler for Halton {
+    fn start(&mut self, x: u32, y: u32, index: u32) {
+        use rand::SeedableRng;
+
+        self.pixel = hash(&[self.seed, x as u64, y as u64]);
+        self.index = index;
+        self.dimension = 0;
+        self.rng = SmallRng::seed_from_u64(hash(&[self.pixel, index as u64]));
+    }
+
+    fn get_1d(&mut self) -> Scalar {
+        use rand::Rng;
+
+        let dimension = self.dimension;
+        self.dimension += 1;
+
+        if dimension >= PRIMES.len() {
+            return self.rng.gen();
+        }
+
+        let shift = (hash(&[self.pixel, dimension as u64]) >> 11) as f64 / (1u64 << 53) as f64;
+        let value = radical_inverse(PRIMES[dimension], self.index) + shift;
+        to_unit(value.fract() as Scalar)
+    }
+
+    fn get_2d(&mut self) -> Vector2 {
+        Vector2::new(self.get_1d(), self.get_1d())
+    }
+}
+
+fn radical_inverse(base: u32, mut index: u32) -> f64 {
+    let inverse_base = 1. / base as f64;
+    let mut scale = inverse_base;
+    let mut value = 0.;
+    while index > 0 {
+        value += (index % base) as f64 * scale;
+        index /= base;
+        scale *= inverse_base;
+    }
+    value
+}
+
+// the first two Sobol dimensions, Owen scrambled, padded to more dimensions
+// by shuffling the order of the samples differently for each pair
+// see Burley, Practical Hash-based Owen Scrambling, 2020
+pub struct Sobol {
+    seed: u64,
+    pixel: u64,
+    index: u32,
+    dimension: u64,
+}
+
+impl Sobol {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed: seed,
+            pixel: 0,
+            index: 0,
+            dimension: 0,
+        }
+    }
+}
+
+impl Sampler for Sobol {
+    fn start(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel = hash(&[self.seed, x as u64, y as u64]);
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> Scalar {
+        self.get_2d().x
+    }
+
+    fn get_2d(&mut self) -> Vector2 {
+        let seed = hash(&[self.pixel, self.dimension]);
+        self.dimension += 1;
+
+        let index = owen_scramble(self.index, seed as u32);
+        let (x, y) = sobol(index);
+        Vector2::new(
+            bits_to_unit(owen_scramble(x, (seed >> 32) as u32)),
+            bits_to_unit(owen_scramble(y, hash(&[seed]) as u32)),
+        )
+    }
+}
+
+// points of the first two dimensions, as fixed point fractions
+fn sobol(index: u32) -> (u32, u32) {
+    let mut x = 0;
+    let mut y = 0;
+    let mut direction: u32 = 1 << 31;
+    let mut index = index;
+    let mut bit = 0;
+    while index > 0 {
+        if index & 1 == 1 {
+            x ^= (1 << 31) >> bit;
+            y ^= direction;
+        }
+        index >>= 1;
+        bit += 1;
+        direction ^= direction >> 1;
+    }
+    (x, y)
+}
+
+fn owen_scramble(x: u32, seed: u32) -> u32 {
+    reverse_bits(laine_karras(reverse_bits(x), seed))
+}
+
+// scrambles higher bits depending only on lower ones
+fn laine_karras(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x
+}
+
+fn reverse_bits(mut x: u32) -> u32 {
+    x = (x << 16) | (x >> 16);
+    x = ((x & 0x00ff_00ff) << 8) | ((x & 0xff00_ff00) >> 8);
+    x = ((x & 0x0f0f_0f0f) << 4) | ((x & 0xf0f0_f0f0) >> 4);
+    x = ((x & 0x3333_3333) << 2) | ((x & 0xcccc_cccc) >> 2);
+    ((x & 0x5555_5555) << 1) | ((x & 0xaaaa_aaaa) >> 1)
+}
+
+// a random permutation of 0..length picked by `seed`, computed one element at a time
+// see Kensler, Correlated Multi-Jittered Sampling, 2013
+fn permute(mut i: u32, length: u32, seed: u32) -> u32 {
+    let mut mask = length.saturating_sub(1);
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= seed >> 16;
+        i ^= (i & mask) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= seed >> 23;
+        i ^= (i & mask) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & mask) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & mask) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & mask) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= mask;
+        i ^= i >> 5;
+        if i < length {
+            break;
+        }
+    }
+    (i.wrapping_add(seed)) % length
+}
+
+// Distribution2D:
+//
+// picks cells of a grid in proportion to their weights,
+// through running sums over each row, and over rows
+//
+#[derive(Debug)]
+pub struct Distribution2D {
+    columns: Vec<Vec<Scalar>>,
+    rows: Vec<Scalar>,
+}
+
+impl Distribution2D {
+    pub fn new<F: FnMut(u32, u32) -> Scalar>(width: u32, height: u32, mut weight: F) -> Self {
+        let columns: Vec<Vec<Scalar>> = (0..height)
+            .map(|y| {
+                let mut total = 0.;
+                (0..width)
+                    .map(|x| {
+                        total += weight(x, y);
+                        total
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut total = 0.;
+        let rows = columns
+            .iter()
+            .map(|row| {
+                total += row.last().cloned().unwrap_or(0.);
+                total
+            })
+            .collect();
+
+        Self {
+            columns: columns,
+            rows: rows,
+        }
+    }
+
+    pub fn total(&self) -> Scalar {
+        self.rows.last().cloned().unwrap_or(0.)
+    }
+
+    // the cell picked by `u`, whose coordinates are in [0, 1), the total must not be 0
+    pub fn sample(&self, u: Vector2) -> (u32, u32) {
+        let y = pick(&self.rows, u.y * self.total());
+        let row = &self.columns[y];
+        let x = pick(row, u.x * row.last().cloned().unwrap_or(0.));
+        (x as u32, y as u32)
+    }
+
+    pub fn weight(&self, x: u32, y: u32) -> Scalar {
+        let row = &self.columns[y as usize];
+        let x = x as usize;
+        if x > 0 {
+            row[x] - row[x - 1]
+        } else {
+            row[x]
+        }
+    }
+}
+
+// index of the entry whose range of a running sum holds `value`
+fn pick(sums: &[Scalar], value: Scalar) -> usize {
+    let i = match sums
+        .binary_search_by(|s| s.partial_cmp(&value).unwrap_or(std::cmp::Ordering::Less))
+    {
+        Ok(i) | Err(i) => i,
+    };
+    i.min(sums.len() - 1)
+}
+
+// the top 24 bits, as many as a float holds
+fn bits_to_unit(x: u32) -> Scalar {
+    (x >> 8) as Scalar / (1 << 24) as Scalar
+}
+
+// keeps rounding from reaching 1
+fn to_unit(x: Scalar) -> Scalar {
+    x.min(1. - std::f32::EPSILON / 2.)
+}
+
+// splitmix64's finalizer folded over `values`, nearby inputs land far apart
+pub(crate) fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0, |h, v| {
+        let mut z = (h ^ v).wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    })
+}
@@ -0,0 +1,770 @@
+use crate::prelude::*;
+
+// Scene files:
+//
+// TOML descriptions of a camera, a background and a list of objects,
+// colors are linear RGB triplets wherever a texture is expected,
+// files such as meshes are found relative to the scene file,
+// orthographic cameras take `projection = { type = "orthographic", width = 2.0 }` instead of `vfov`,
+// and panoramas `{ type = "equirectangular" }` or `{ type = "fisheye", fov = 180.0 }`
+//
+// [camera]
+// from = [0.0, 0.0, -3.75]
+// at = [0.0, 0.0, 1.0]
+// vfov = 40.0
+// resolution = [500, 500]
+// samples = 1000
+// seed = 42
+// sampler = "sobol"
+// adaptive = { threshold = 0.01, min_samples = 64 }
+// bokeh = { type = "polygon", blades = 6, rotation = 15.0 }
+// cat_eye = 0.3
+// stereo = { interocular = 0.064, convergence = 2.0, layout = "top_bottom" }
+// tone_map = { type = "extended_reinhard", white = 4.0 }
+//
+// [[objects]]
+// shape = { type = "ball", radius = 0.3 }
+// material = { type = "metal", albedo = [0.0, 0.0, 1.0], fuzz = 0.1 }
+// translation = [-0.3, -0.6, -0.3]
+//
+// see scenes/ for complete examples
+pub fn load<P: AsRef<std::path::Path>>(path: P) -> Result<(Scene, Camera), failure::Error> {
+    // paths inside the file are relative to it
+    let base = path.as_ref().parent().unwrap_or(std::path::Path::new(""));
+    let description: SceneFile = toml::from_str(&std::fs::read_to_string(&path)?)?;
+
+    let camera = description.camera.build(base)?;
+    let background = description.background.build(base)?;
+    let objects = description
+        .objects
+        .into_iter()
+        .map(|object| object.build(base))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((Scene::new(background, objects), camera))
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    camera: CameraDesc,
+    #[serde(default = "default_background")]
+    background: BackgroundDesc,
+    #[serde(default)]
+    objects: Vec<ObjectDesc>,
+}
+
+fn default_background() -> BackgroundDesc {
+    BackgroundDesc::Gradient(vec![[0.4, 0.5, 1.], [1., 1., 1.], [0.4, 0.5, 1.]])
+}
+
+// either a gradient from bottom to top, or `{ type = "environment", path = "sky.hdr" }`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BackgroundDesc {
+    Gradient(Vec<[Scalar; 3]>),
+    Background(BackgroundKind),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BackgroundKind {
+    Environment {
+        path: std::path::PathBuf,
+        // axis-angle, in radians
+        #[serde(default)]
+        rotation: [Scalar; 3],
+        #[serde(default = "default_intensity")]
+        intensity: Scalar,
+    },
+}
+
+fn default_intensity() -> Scalar {
+    1.
+}
+
+impl BackgroundDesc {
+    fn build(&self, base: &std::path::Path) -> Result<Box<Background>, failure::Error> {
+        let kind = match self {
+            BackgroundDesc::Gradient(colors) => return Ok(Box::new(gradient(colors)?)),
+            BackgroundDesc::Background(kind) => kind,
+        };
+
+        let background: Box<Background> = match kind {
+            BackgroundKind::Environment {
+                path,
+                rotation,
+                intensity,
+            } => Box::new(Environment::new(
+                Framebuffer::open(base.join(path), false)?,
+                na::UnitQuaternion::new(vector(*rotation)),
+                *intensity,
+            )),
+        };
+        Ok(background)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDesc {
+    from: [Scalar; 3],
+    at: [Scalar; 3],
+    #[serde(default = "default_up")]
+    up: [Scalar; 3],
+    #[serde(default)]
+    projection: ProjectionDesc,
+    // perspective projections only
+    vfov: Option<Scalar>,
+    #[serde(default)]
+    aperture: Scalar,
+    focus_dist: Option<Scalar>,
+    // 0 freezes moving objects where they start
+    #[serde(default = "default_shutter_speed")]
+    shutter_speed: Scalar,
+    resolution: [u32; 2],
+    samples: u32,
+    #[serde(default)]
+    tone_map: ToneMapDesc,
+    #[serde(default)]
+    seed: u64,
+    #[serde(default)]
+    sampler: SamplerDesc,
+    adaptive: Option<AdaptiveDesc>,
+    stereo: Option<StereoDesc>,
+    #[serde(default)]
+    bokeh: BokehDesc,
+    #[serde(default)]
+    cat_eye: Scalar,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BokehDesc {
+    Circle,
+    Polygon {
+        blades: u32,
+        #[serde(default)]
+        rotation: Scalar,
+    },
+    Mask {
+        path: std::path::PathBuf,
+    },
+}
+
+impl Default for BokehDesc {
+    fn default() -> Self {
+        BokehDesc::Circle
+    }
+}
+
+impl BokehDesc {
+    fn build(&self, base: &std::path::Path) -> Result<ApertureShape, failure::Error> {
+        Ok(match self {
+            BokehDesc::Circle => ApertureShape::Circle,
+            BokehDesc::Polygon { blades, rotation } => ApertureShape::Polygon {
+                blades: *blades,
+                rotation: *rotation,
+            },
+            // transmission, not color
+            BokehDesc::Mask { path } => ApertureShape::Mask(Arc::new(ApertureMask::new(
+                &Framebuffer::open(base.join(path), true)?,
+            ))),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StereoDesc {
+    #[serde(default = "default_interocular")]
+    interocular: Scalar,
+    convergence: Option<Scalar>,
+    #[serde(default)]
+    layout: StereoLayoutDesc,
+}
+
+fn default_interocular() -> Scalar {
+    0.064
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum StereoLayoutDesc {
+    SideBySide,
+    TopBottom,
+}
+
+impl Default for StereoLayoutDesc {
+    fn default() -> Self {
+        StereoLayoutDesc::SideBySide
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AdaptiveDesc {
+    #[serde(default = "default_min_samples")]
+    min_samples: u32,
+    threshold: Scalar,
+}
+
+fn default_min_samples() -> u32 {
+    64
+}
+
+fn default_up() -> [Scalar; 3] {
+    [0., 1., 0.]
+}
+
+fn default_shutter_speed() -> Scalar {
+    1. / 500.
+}
+
+impl CameraDesc {
+    fn build(&self, base: &std::path::Path) -> Result<Camera, failure::Error> {
+        let resolution = na::Vector2::new(self.resolution[0], self.resolution[1]);
+        // eyes converge at that distance in front of the camera, nowhere else makes sense
+        let convergence = self.stereo.as_ref().and_then(|stereo| stereo.convergence);
+        if convergence.map_or(false, |convergence| !(convergence > 0.)) {
+            return Err(failure::format_err!("convergence must be positive"));
+        }
+        if let (Some(_), ProjectionDesc::Orthographic { .. }) = (convergence, self.projection) {
+            return Err(failure::format_err!("orthographic stereo can't converge"));
+        }
+        let vfov = match self.projection {
+            ProjectionDesc::Perspective => self
+                .vfov
+                .ok_or_else(|| failure::format_err!("a perspective camera needs a vfov"))?,
+            _ => self.vfov.unwrap_or(90.),
+        };
+        let perspective = Camera::new(
+            &point(self.from),
+            &point(self.at),
+            &vector(self.up),
+            vfov,
+            self.aperture,
+            self.focus_dist,
+            self.shutter_speed,
+            resolution,
+            self.samples,
+        );
+        let camera = match self.projection {
+            ProjectionDesc::Perspective => perspective,
+            ProjectionDesc::Orthographic { width } => Camera::orthographic(
+                &point(self.from),
+                &point(self.at),
+                &vector(self.up),
+                width,
+                self.shutter_speed,
+                resolution,
+                self.samples,
+            ),
+            ProjectionDesc::Equirectangular => {
+                perspective.with_projection(Projection::Equirectangular)
+            }
+            ProjectionDesc::Fisheye { fov } => {
+                perspective.with_projection(Projection::Fisheye { fov: fov })
+            }
+        };
+
+        Ok(camera
+            .with_tone_map(self.tone_map.build())
+            .with_seed(self.seed)
+            .with_sampler(match self.sampler {
+                SamplerDesc::Independent => SamplerKind::Independent,
+                SamplerDesc::Stratified => SamplerKind::Stratified,
+                SamplerDesc::Halton => SamplerKind::Halton,
+                SamplerDesc::Sobol => SamplerKind::Sobol,
+            })
+            .with_adaptive(self.adaptive.as_ref().map(|adaptive| Adaptive {
+                min_samples: adaptive.min_samples,
+                threshold: adaptive.threshold,
+            }))
+            .with_stereo(self.stereo.as_ref().map(|stereo| Stereo {
+                interocular: stereo.interocular,
+                convergence: stereo.convergence,
+                layout: match stereo.layout {
+                    StereoLayoutDesc::SideBySide => StereoLayout::SideBySide,
+                    StereoLayoutDesc::TopBottom => StereoLayout::TopBottom,
+                },
+            }))
+            .with_aperture_shape(self.bokeh.build(base)?)
+            .with_cat_eye(self.cat_eye))
+    }
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ProjectionDesc {
+    Perspective,
+    Orthographic {
+        width: Scalar,
+    },
+    Equirectangular,
+    Fisheye {
+        #[serde(default = "default_fisheye_fov")]
+        fov: Scalar,
+    },
+}
+
+fn default_fisheye_fov() -> Scalar {
+    180.
+}
+
+impl Default for ProjectionDesc {
+    fn default() -> Self {
+        ProjectionDesc::Perspective
+    }
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SamplerDesc {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+impl Default for SamplerDesc {
+    fn default() -> Self {
+        SamplerDesc::Independent
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ToneMapDesc {
+    Clamp,
+    Reinhard,
+    ExtendedReinhard {
+        white: Scalar,
+    },
+    AcesFilmic,
+    Exposure {
+        #[serde(default)]
+        stops: Scalar,
+        #[serde(default = "one")]
+        gamma: Scalar,
+    },
+}
+
+impl Default for ToneMapDesc {
+    fn default() -> Self {
+        ToneMapDesc::Clamp
+    }
+}
+
+fn one() -> Scalar {
+    1.
+}
+
+impl ToneMapDesc {
+    fn build(&self) -> Box<ToneMap> {
+        match *self {
+            ToneMapDesc::Clamp => Box::new(Clamp),
+            ToneMapDesc::Reinhard => Box::new(Reinhard),
+            ToneMapDesc::ExtendedReinhard { white } => Box::new(ExtendedReinhard { white: white }),
+            ToneMapDesc::AcesFilmic => Box::new(AcesFilmic),
+            ToneMapDesc::Exposure { stops, gamma } => Box::new(Exposure {
+                stops: stops,
+                gamma: gamma,
+            }),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ObjectDesc {
+    shape: ShapeDesc,
+    material: MaterialDesc,
+    #[serde(default)]
+    translation: [Scalar; 3],
+    // axis-angle, in radians
+    #[serde(default)]
+    rotation: [Scalar; 3],
+    translation_end: Option<[Scalar; 3]>,
+    rotation_end: Option<[Scalar; 3]>,
+}
+
+impl ObjectDesc {
+    fn build(self, base: &std::path::Path) -> Result<(Arc<Object>, AABB), failure::Error> {
+        let transform = Isometry::new(vector(self.translation), vector(self.rotation));
+        let transform_end = Isometry::new(
+            vector(self.translation_end.unwrap_or(self.translation)),
+            vector(self.rotation_end.unwrap_or(self.rotation)),
+        );
+
+        Ok(mkObject!({
+            shape: self.shape.build(base)?,
+            material: self.material.build(base)?,
+            transform: transform,
+            transform_end: transform_end,
+        }))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ShapeDesc {
+    Ball {
+        radius: Scalar,
+    },
+    Cuboid {
+        half_extents: [Scalar; 3],
+    },
+    ConstantMedium {
+        shape: Box<ShapeDesc>,
+        density: Scalar,
+    },
+    Mesh {
+        path: std::path::PathBuf,
+    },
+}
+
+impl ShapeDesc {
+    fn build(&self, base: &std::path::Path) -> Result<BoxedShape, failure::Error> {
+        let shape: Box<Geometry> = match self {
+            ShapeDesc::Ball { radius } => Box::new(nc::shape::Ball::new(*radius)),
+            ShapeDesc::Cuboid { half_extents } => {
+                Box::new(nc::shape::Cuboid::new(vector(*half_extents)))
+            }
+            ShapeDesc::ConstantMedium { shape, density } => Box::new(ConstantMedium {
+                shape: shape.build(base)?,
+                density: *density,
+            }),
+            ShapeDesc::Mesh { path } => Box::new(Mesh::load_obj(base.join(path))?),
+        };
+        Ok(BoxedShape(shape))
+    }
+}
+
+// lets descriptions nest shapes without knowing their types, ConstantMedium included
+struct BoxedShape(Box<Geometry>);
+
+impl nc::shape::Shape<Scalar> for BoxedShape {
+    fn aabb(&self, m: &Isometry) -> AABB {
+        self.0.aabb(m)
+    }
+}
+
+impl Geometry for BoxedShape {
+    fn ray_cast(&self, m: &Isometry, ray: &Ray, sampler: &mut Sampler) -> Option<RayIntersection> {
+        self.0.ray_cast(m, ray, sampler)
+    }
+
+    fn area(&self) -> Scalar {
+        self.0.area()
+    }
+
+    fn sample_surface(&self, m: &Isometry, sampler: &mut Sampler) -> Option<(Point, Vector)> {
+        self.0.sample_surface(m, sampler)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum MaterialDesc {
+    Lambertian {
+        albedo: TextureDesc,
+    },
+    Metal {
+        albedo: TextureDesc,
+        #[serde(default)]
+        fuzz: Scalar,
+    },
+    Dielectric {
+        refraction: Scalar,
+        #[serde(default = "white")]
+        attenuation: TextureDesc,
+    },
+    DiffuseLight {
+        value: TextureDesc,
+    },
+    Isotropic {
+        albedo: TextureDesc,
+    },
+}
+
+fn white() -> TextureDesc {
+    TextureDesc::Color([1., 1., 1.])
+}
+
+impl MaterialDesc {
+    fn build(&self, base: &std::path::Path) -> Result<Box<Material + Send + Sync>, failure::Error> {
+        let material: Box<Material + Send + Sync> = match self {
+            MaterialDesc::Lambertian { albedo } => Box::new(Lambertian {
+                albedo: albedo.build(base)?,
+            }),
+            MaterialDesc::Metal { albedo, fuzz } => Box::new(Metal {
+                albedo: albedo.build(base)?,
+                fuzz: *fuzz,
+            }),
+            MaterialDesc::Dielectric {
+                refraction,
+                attenuation,
+            } => Box::new(Dielectric {
+                refraction: *refraction,
+                attenuation: attenuation.build(base)?,
+            }),
+            MaterialDesc::DiffuseLight { value } => Box::new(DiffuseLight {
+                value: value.build(base)?,
+            }),
+            MaterialDesc::Isotropic { albedo } => Box::new(Isotropic {
+                albedo: albedo.build(base)?,
+            }),
+        };
+        Ok(material)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TextureDesc {
+    Color([Scalar; 3]),
+    Texture(TextureKind),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum TextureKind {
+    Srgb {
+        value: [Scalar; 3],
+    },
+    Gradient {
+        colors: Vec<[Scalar; 3]>,
+    },
+    Noise2d {
+        noise: NoiseKind,
+        #[serde(default)]
+        seed: u32,
+        scale: [Scalar; 2],
+        gradient: Vec<[Scalar; 3]>,
+    },
+    Noise3d {
+        noise: NoiseKind,
+        #[serde(default)]
+        seed: u32,
+        scale: [Scalar; 3],
+        gradient: Vec<[Scalar; 3]>,
+    },
+    Checkerboard {
+        even: Box<TextureDesc>,
+        odd: Box<TextureDesc>,
+        size: Scalar,
+    },
+    Image {
+        path: std::path::PathBuf,
+        #[serde(default = "default_filter")]
+        filter: FilterDesc,
+        #[serde(default = "default_wrap")]
+        wrap: WrapDesc,
+        // for 8-bit files holding data rather than colors
+        #[serde(default)]
+        linear: bool,
+    },
+    DebugUv,
+    DebugPoint,
+    DebugNormal,
+    DebugDistance,
+}
+
+impl TextureDesc {
+    fn build(&self, base: &std::path::Path) -> Result<Box<Texture + Send + Sync>, failure::Error> {
+        let kind = match self {
+            TextureDesc::Color(c) => return Ok(Box::new(color(*c))),
+            TextureDesc::Texture(kind) => kind,
+        };
+
+        let texture: Box<Texture + Send + Sync> = match kind {
+            TextureKind::Srgb { value } => {
+                Box::new(palette::Srgb::new(value[0], value[1], value[2]).into_linear())
+            }
+            TextureKind::Gradient { colors } => Box::new(gradient(colors)?),
+            TextureKind::Noise2d {
+                noise,
+                seed,
+                scale,
+                gradient: colors,
+            } => Box::new(Noise2D {
+                gradient: gradient(colors)?,
+                noise: noise.build(*seed),
+                scale: Vector2::new(scale[0], scale[1]),
+            }),
+            TextureKind::Noise3d {
+                noise,
+                seed,
+                scale,
+                gradient: colors,
+            } => Box::new(Noise3D {
+                gradient: gradient(colors)?,
+                noise: noise.build(*seed),
+                scale: vector(*scale),
+            }),
+            TextureKind::Checkerboard { even, odd, size } => Box::new(Checkerboard {
+                even: even.build(base)?,
+                odd: odd.build(base)?,
+                size: *size,
+            }),
+            TextureKind::Image {
+                path,
+                filter,
+                wrap,
+                linear,
+            } => Box::new(ImageTexture {
+                image: Framebuffer::open(base.join(path), *linear)?,
+                filter: match filter {
+                    FilterDesc::Nearest => Filter::Nearest,
+                    FilterDesc::Bilinear => Filter::Bilinear,
+                },
+                wrap: match wrap {
+                    WrapDesc::Repeat => Wrap::Repeat,
+                    WrapDesc::Mirror => Wrap::Mirror,
+                    WrapDesc::Clamp => Wrap::Clamp,
+                },
+            }),
+            TextureKind::DebugUv => Box::new(DebugUV),
+            TextureKind::DebugPoint => Box::new(DebugPoint),
+            TextureKind::DebugNormal => Box::new(DebugNormal),
+            TextureKind::DebugDistance => Box::new(DebugDistance),
+        };
+        Ok(texture)
+    }
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum FilterDesc {
+    Nearest,
+    Bilinear,
+}
+
+fn default_filter() -> FilterDesc {
+    FilterDesc::Bilinear
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum WrapDesc {
+    Repeat,
+    Mirror,
+    Clamp,
+}
+
+fn default_wrap() -> WrapDesc {
+    WrapDesc::Repeat
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum NoiseKind {
+    Perlin,
+    Value,
+    Worley,
+    OpenSimplex,
+    SuperSimplex,
+    Fbm,
+    Billow,
+    BasicMulti,
+    HybridMulti,
+    RidgedMulti,
+}
+
+impl NoiseKind {
+    fn build(self, seed: u32) -> Noise {
+        use noise::Seedable;
+
+        match self {
+            NoiseKind::Perlin => Noise::Perlin(noise::Perlin::new().set_seed(seed)),
+            NoiseKind::Value => Noise::Value(noise::Value::new().set_seed(seed)),
+            NoiseKind::Worley => Noise::Worley(noise::Worley::new().set_seed(seed)),
+            NoiseKind::OpenSimplex => Noise::OpenSimplex(noise::OpenSimplex::new().set_seed(seed)),
+            NoiseKind::SuperSimplex => {
+                Noise::SuperSimplex(noise::SuperSimplex::new().set_seed(seed))
+            }
+            NoiseKind::Fbm => Noise::Fbm(noise::Fbm::new().set_seed(seed)),
+            NoiseKind::Billow => Noise::Billow(noise::Billow::new().set_seed(seed)),
+            NoiseKind::BasicMulti => Noise::BasicMulti(noise::BasicMulti::new().set_seed(seed)),
+            NoiseKind::HybridMulti => Noise::HybridMulti(noise::HybridMulti::new().set_seed(seed)),
+            NoiseKind::RidgedMulti => Noise::RidgedMulti(noise::RidgedMulti::new().set_seed(seed)),
+        }
+    }
+}
+
+// noise functions can't be boxed, Noise2D and Noise3D get one of these instead
+enum Noise {
+    Perlin(noise::Perlin),
+    Value(noise::Value),
+    Worley(noise::Worley),
+    OpenSimplex(noise::OpenSimplex),
+    SuperSimplex(noise::SuperSimplex),
+    Fbm(noise::Fbm),
+    Billow(noise::Billow),
+    BasicMulti(noise::BasicMulti),
+    HybridMulti(noise::HybridMulti),
+    RidgedMulti(noise::RidgedMulti),
+}
+
+impl Noise {
+    fn value<P>(&self, point: P) -> f64
+    where
+        noise::Perlin: noise::NoiseFn<P>,
+        noise::Value: noise::NoiseFn<P>,
+        noise::Worley: noise::NoiseFn<P>,
+        noise::OpenSimplex: noise::NoiseFn<P>,
+        noise::SuperSimplex: noise::NoiseFn<P>,
+        noise::Fbm: noise::NoiseFn<P>,
+        noise::Billow: noise::NoiseFn<P>,
+        noise::BasicMulti: noise::NoiseFn<P>,
+        noise::HybridMulti: noise::NoiseFn<P>,
+        noise::RidgedMulti: noise::NoiseFn<P>,
+    {
+        use noise::NoiseFn;
+
+        match self {
+            Noise::Perlin(n) => n.get(point),
+            Noise::Value(n) => n.get(point),
+            Noise::Worley(n) => n.get(point),
+            Noise::OpenSimplex(n) => n.get(point),
+            Noise::SuperSimplex(n) => n.get(point),
+            Noise::Fbm(n) => n.get(point),
+            Noise::Billow(n) => n.get(point),
+            Noise::BasicMulti(n) => n.get(point),
+            Noise::HybridMulti(n) => n.get(point),
+            Noise::RidgedMulti(n) => n.get(point),
+        }
+    }
+}
+
+impl noise::NoiseFn<[f64; 2]> for Noise {
+    fn get(&self, point: [f64; 2]) -> f64 {
+        self.value(point)
+    }
+}
+
+impl noise::NoiseFn<[f64; 3]> for Noise {
+    fn get(&self, point: [f64; 3]) -> f64 {
+        self.value(point)
+    }
+}
+
+fn gradient(colors: &[[Scalar; 3]]) -> Result<palette::Gradient<Color>, failure::Error> {
+    if colors.is_empty() {
+        return Err(failure::format_err!("a gradient needs at least one color"));
+    }
+    Ok(palette::Gradient::new(colors.iter().map(|c| color(*c))))
+}
+
+fn color(c: [Scalar; 3]) -> Color {
+    Color::new(c[0], c[1], c[2])
+}
+
+fn point(p: [Scalar; 3]) -> Point {
+    Point::new(p[0], p[1], p[2])
+}
+
+fn vector(v: [Scalar; 3]) -> Vector {
+    Vector::new(v[0], v[1], v[2])
+}
@@ -0,0 +1,219 @@
+use crate::prelude::*;
+
+mod file;
+
+pub use self::file::load;
+
+pub struct Scene {
+    pub background: Box<Background>,
+    pub objects: BVT,
+    pub lights: Vec<Arc<Object>>,
+}
+
+impl Scene {
+    pub fn new<B: Background + 'static>(background: B, objects: Vec<(Arc<Object>, AABB)>) -> Self {
+        let lights = objects
+            .iter()
+            .filter(|(object, _)| object.is_light())
+            .map(|(object, _)| object.clone())
+            .collect();
+
+        Self {
+            background: Box::new(background),
+            objects: BVT::new_balanced(objects),
+            lights: lights,
+        }
+    }
+
+    pub fn trace(&self, init_ray: &Ray, sampler: &mut Sampler, stats: &mut RenderStats) -> Color {
+        let mut ray = *init_ray;
+        let mut color = Color::default();
+        let mut attenuation = Color::new(1., 1., 1.);
+        // density of the scatter that produced `ray`, 0 for camera rays and perfect reflections
+        let mut scatter_pdf = 0.;
+
+        for _ in 0..50 {
+            stats.rays += 1;
+            let search_result = self.intersect(&ray, sampler, stats);
+
+            if search_result.is_none() {
+                let dir = ray.dir.normalize();
+                // the previous vertex also sampled the background through sample_lights
+                let weight = if scatter_pdf > 0. && self.background.is_light() {
+                    power_heuristic(
+                        scatter_pdf,
+                        self.background.pdf(&dir) / self.light_count() as Scalar,
+                    )
+                } else {
+                    1.
+                };
+                color = color + attenuation * self.background.radiance(&dir) * weight;
+                break;
+            }
+
+            let (object, intersection) = search_result.unwrap();
+
+            // the previous vertex also reached this light through sample_lights
+            let weight = if scatter_pdf > 0. && object.is_light() {
+                power_heuristic(scatter_pdf, self.light_pdf(&ray, &intersection, &**object))
+            } else {
+                1.
+            };
+            let emitted = object.material_emitted(&ray, &intersection);
+            color = color + attenuation * emitted * weight;
+
+            let direct = self.sample_lights(&ray, &intersection, &**object, sampler, stats);
+            color = color + attenuation * direct;
+
+            let wo = -ray.dir.normalize();
+            let sample = object.material_sample(&ray, &intersection, &wo, sampler);
+
+            if sample.is_none() {
+                break;
+            }
+
+            let sample = sample.unwrap();
+            scatter_pdf = if sample.lobe.contains(Lobe::SPECULAR) {
+                0.
+            } else {
+                sample.pdf
+            };
+            ray = Ray {
+                origin: intersection.point_nudged_toward(&ray, &sample.wi),
+                dir: sample.wi,
+                time: ray.time,
+            };
+            attenuation = attenuation * sample.weight;
+
+            if attenuation.red + attenuation.green + attenuation.blue < 0.0003 {
+                break;
+            }
+        }
+        color
+    }
+
+    // next-event estimation: light arriving directly from a point picked on one of the lights,
+    // or from the background, weighted against the material picking the same direction
+    fn sample_lights(
+        &self,
+        ray: &Ray,
+        intersection: &RayIntersection,
+        object: &Object,
+        sampler: &mut Sampler,
+        stats: &mut RenderStats,
+    ) -> Color {
+        if self.light_count() == 0 || !object.material_lobes().is_smooth() {
+            return Color::default();
+        }
+
+        let index = ((sampler.get_1d() * self.light_count() as Scalar) as usize)
+            .min(self.light_count() - 1);
+        if index == self.lights.len() {
+            return self.sample_background(ray, intersection, object, sampler, stats);
+        }
+
+        let light = &self.lights[index];
+        let (point, normal, pdf) = match light.sample_surface(ray.time, sampler) {
+            Some(sample) => sample,
+            None => return Color::default(),
+        };
+
+        let origin = intersection.point_nudged_toward(&ray, &(point - intersection.point(&ray)));
+        let dir = point - origin;
+        let distance = dir.magnitude();
+        let wo = -ray.dir.normalize();
+        let wi = dir / distance;
+        let cosine = normal.dot(&dir).abs() / distance;
+        let light_pdf = pdf * distance * distance / (cosine * self.light_count() as Scalar);
+        if cosine <= 0. || !light_pdf.is_finite() {
+            return Color::default();
+        }
+
+        let shadow_ray = Ray {
+            origin: origin,
+            dir: dir,
+            time: ray.time,
+        };
+
+        stats.shadow_rays += 1;
+        match self.intersect(&shadow_ray, sampler, stats) {
+            Some((hit, light_intersection))
+                if Arc::ptr_eq(hit, light)
+                    && (1. - light_intersection.toi) * distance < ANTI_ACNE =>
+            {
+                let emitted = light.material_emitted(&shadow_ray, &light_intersection);
+                let bsdf = object.material_eval(ray, intersection, &wo, &wi);
+                let scatter_pdf = object.material_pdf(ray, intersection, &wo, &wi);
+                let weight = power_heuristic(light_pdf, scatter_pdf) / light_pdf;
+                emitted * bsdf * weight
+            }
+            _ => Color::default(),
+        }
+    }
+
+    fn sample_background(
+        &self,
+        ray: &Ray,
+        intersection: &RayIntersection,
+        object: &Object,
+        sampler: &mut Sampler,
+        stats: &mut RenderStats,
+    ) -> Color {
+        let (wi, pdf) = match self.background.sample(sampler) {
+            Some(sample) => sample,
+            None => return Color::default(),
+        };
+
+        let wo = -ray.dir.normalize();
+        let light_pdf = pdf / self.light_count() as Scalar;
+        let shadow_ray = Ray {
+            origin: intersection.point_nudged_toward(&ray, &wi),
+            dir: wi,
+            time: ray.time,
+        };
+
+        stats.shadow_rays += 1;
+        if self.intersect(&shadow_ray, sampler, stats).is_some() {
+            return Color::default();
+        }
+
+        let bsdf = object.material_eval(ray, intersection, &wo, &wi);
+        let scatter_pdf = object.material_pdf(ray, intersection, &wo, &wi);
+        let weight = power_heuristic(light_pdf, scatter_pdf) / light_pdf;
+        self.background.radiance(&wi) * bsdf * weight
+    }
+
+    // the closest object along `ray`
+    fn intersect(
+        &self,
+        ray: &Ray,
+        sampler: &mut Sampler,
+        stats: &mut RenderStats,
+    ) -> Option<(&Arc<Object>, RayIntersection)> {
+        let mut cost = CostByRayCast {
+            ray: ray,
+            sampler: sampler,
+            visits: 0,
+        };
+        let hit = self.objects.best_first_search(&mut cost);
+        stats.node_visits += cost.visits;
+        hit
+    }
+
+    // the background counts as one more light when it can be sampled
+    fn light_count(&self) -> usize {
+        self.lights.len() + self.background.is_light() as usize
+    }
+
+    // density by solid angle of sample_lights picking the point `ray` hit on `light`
+    fn light_pdf(&self, ray: &Ray, intersection: &RayIntersection, light: &Object) -> Scalar {
+        let distance = intersection.toi * ray.dir.magnitude();
+        let cosine = intersection.normal.dot(&ray.dir).abs() / ray.dir.magnitude();
+        light.surface_pdf() * distance * distance / (cosine * self.light_count() as Scalar)
+    }
+}
+
+fn power_heuristic(pdf: Scalar, other_pdf: Scalar) -> Scalar {
+    let pdf = pdf * pdf;
+    pdf / (pdf + other_pdf * other_pdf)
+}
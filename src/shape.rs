@@ -1,42 +1,88 @@
 use crate::prelude::*;
 
-pub struct ConstantMedium<S: nc::shape::Shape<Scalar>> {
-    pub shape: S,
-    pub density: Scalar,
+// Geometry:
+//
+// what the tracer needs from a shape on top of ncollide's queries,
+// shapes that can't be sampled have no area and are never used as lights
+//
+pub trait Geometry: nc::shape::Shape<Scalar> {
+    fn ray_cast(&self, m: &Isometry, ray: &Ray, _sampler: &mut Sampler) -> Option<RayIntersection> {
+        self.as_ray_cast()?
+            .toi_and_normal_and_uv_with_ray(m, &ray.as_nc(), false)
+    }
+
+    fn area(&self) -> Scalar {
+        0.
+    }
+
+    // uniform by area, returns the point and the outward normal
+    fn sample_surface(&self, _m: &Isometry, _sampler: &mut Sampler) -> Option<(Point, Vector)> {
+        None
+    }
 }
 
-impl<S: nc::shape::Shape<Scalar>> nc::shape::Shape<Scalar> for ConstantMedium<S> {
-    fn aabb(&self, m: &Isometry) -> AABB {
-        self.shape.aabb(m)
+impl Geometry for nc::shape::Ball<Scalar> {
+    fn area(&self) -> Scalar {
+        4. * std::f32::consts::PI * self.radius() * self.radius()
     }
 
-    fn as_ray_cast(&self) -> Option<&RayCast> {
-        Some(self)
+    fn sample_surface(&self, m: &Isometry, sampler: &mut Sampler) -> Option<(Point, Vector)> {
+        let normal = Vector::random_on_sphere(sampler);
+        Some((
+            m * Point::from_coordinates(normal * self.radius()),
+            m * normal,
+        ))
     }
 }
 
-impl<S: nc::shape::Shape<Scalar>> nc::query::RayCast<Scalar> for ConstantMedium<S> {
-    fn toi_and_normal_with_ray(
-        &self,
-        m: &Isometry,
-        ray: &Ray,
-        solid: bool,
-    ) -> Option<RayIntersection> {
-        let intersection1 = self
-            .shape
-            .as_ray_cast()?
-            .toi_and_normal_with_ray(m, ray, solid)?;
+impl Geometry for nc::shape::Cuboid<Scalar> {
+    fn area(&self) -> Scalar {
+        let e = self.half_extents();
+        8. * (e.x * e.y + e.y * e.z + e.z * e.x)
+    }
+
+    fn sample_surface(&self, m: &Isometry, sampler: &mut Sampler) -> Option<(Point, Vector)> {
+        let e = self.half_extents();
+        let faces = [e.y * e.z, e.z * e.x, e.x * e.y];
+
+        // pick an axis weighted by the area of its two faces, then a side
+        let mut pick = sampler.get_1d() * (faces[0] + faces[1] + faces[2]);
+        let mut axis = 0;
+        while axis < 2 && pick >= faces[axis] {
+            pick -= faces[axis];
+            axis += 1;
+        }
+        let side = if pick < faces[axis] / 2. { 1. } else { -1. };
+
+        let uv = sampler.get_2d() * 2. - Vector2::new(1., 1.);
+        let mut point = Vector::zeros();
+        let mut normal = Vector::zeros();
+        point[(axis + 1) % 3] = uv.x * e[(axis + 1) % 3];
+        point[(axis + 2) % 3] = uv.y * e[(axis + 2) % 3];
+        point[axis] = side * e[axis];
+        normal[axis] = side;
+
+        Some((m * Point::from_coordinates(point), m * normal))
+    }
+}
+
+pub struct ConstantMedium<S: Geometry> {
+    pub shape: S,
+    pub density: Scalar,
+}
+
+impl<S: Geometry> Geometry for ConstantMedium<S> {
+    fn ray_cast(&self, m: &Isometry, ray: &Ray, sampler: &mut Sampler) -> Option<RayIntersection> {
+        let intersection1 = self.shape.ray_cast(m, ray, sampler)?;
         let new_ray = Ray {
             origin: intersection1.point_nudged_in(&ray),
             dir: ray.dir,
+            time: ray.time,
         };
-        let intersection2 = self
-            .shape
-            .as_ray_cast()?
-            .toi_and_normal_with_ray(m, &new_ray, solid)?;
+        let intersection2 = self.shape.ray_cast(m, &new_ray, sampler)?;
 
         let distance_through = intersection2.toi * ray.dir.magnitude();
-        let hit_distance = -(1. / self.density) * rand::random::<Scalar>().ln();
+        let hit_distance = -(1. / self.density) * sampler.get_1d().ln();
 
         if hit_distance >= distance_through {
             return None;
@@ -49,3 +95,9 @@ impl<S: nc::shape::Shape<Scalar>> nc::query::RayCast<Scalar> for ConstantMedium<
         })
     }
 }
+
+impl<S: Geometry> nc::shape::Shape<Scalar> for ConstantMedium<S> {
+    fn aabb(&self, m: &Isometry) -> AABB {
+        self.shape.aabb(m)
+    }
+}
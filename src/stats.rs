@@ -0,0 +1,90 @@
+use std::io::Write;
+
+// RenderStats:
+//
+// what a render cost, counted by each tile and summed up
+// `rays` are the segments of paths, starting with camera rays,
+// shadow rays are traced on top of those toward lights and the background
+//
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderStats {
+    // one per sample, even those the projection doesn't reach or the lens cuts off
+    pub camera_rays: u64,
+    pub rays: u64,
+    pub shadow_rays: u64,
+    // bounding volumes of the scene's tree tested against rays,
+    // those of each mesh's own tree aren't counted
+    pub node_visits: u64,
+    pub wall_time: std::time::Duration,
+}
+
+impl RenderStats {
+    pub fn add(&mut self, other: &RenderStats) {
+        self.camera_rays += other.camera_rays;
+        self.rays += other.rays;
+        self.shadow_rays += other.shadow_rays;
+        self.node_visits += other.node_visits;
+    }
+
+    // segments per camera ray
+    pub fn average_path_length(&self) -> f64 {
+        if self.camera_rays == 0 {
+            return 0.;
+        }
+        self.rays as f64 / self.camera_rays as f64
+    }
+
+    // shadow rays included
+    pub fn rays_per_second(&self) -> f64 {
+        let seconds = seconds(self.wall_time);
+        if seconds == 0. {
+            return 0.;
+        }
+        (self.rays + self.shadow_rays) as f64 / seconds
+    }
+
+    pub fn write_json<W: Write>(&self, w: &mut W) -> Result<(), failure::Error> {
+        serde_json::to_writer_pretty(&mut *w, &self.to_json())?;
+        writeln!(w)?;
+        Ok(())
+    }
+
+    pub(crate) fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "camera_rays": self.camera_rays,
+            "rays": self.rays,
+            "shadow_rays": self.shadow_rays,
+            "node_visits": self.node_visits,
+            "average_path_length": self.average_path_length(),
+            "wall_time": seconds(self.wall_time),
+            "rays_per_second": self.rays_per_second(),
+        })
+    }
+}
+
+impl std::fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "camera rays:         {}", self.camera_rays)?;
+        writeln!(f, "rays:                {}", self.rays)?;
+        writeln!(f, "shadow rays:         {}", self.shadow_rays)?;
+        writeln!(f, "node visits:         {}", self.node_visits)?;
+        writeln!(f, "average path length: {:.2}", self.average_path_length())?;
+        writeln!(f, "wall time:           {:.2}s", seconds(self.wall_time))?;
+        write!(f, "rays per second:     {}", si(self.rays_per_second()))
+    }
+}
+
+pub(crate) fn seconds(duration: std::time::Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1e9
+}
+
+// with a metric prefix
+pub(crate) fn si(value: f64) -> String {
+    match value {
+        value if value >= 1e+12 => format!("{:.2} tera", value / 1e+12),
+        value if value >= 1e+09 => format!("{:.2} giga", value / 1e+09),
+        value if value >= 1e+06 => format!("{:.2} mega", value / 1e+06),
+        value if value >= 1e+03 => format!("{:.2} kilo", value / 1e+03),
+        _ => format!("{:.2}", value),
+    }
+}
@@ -4,6 +4,12 @@ pub trait Texture {
     fn sample(&self, ray: &Ray, intersection: &RayIntersection) -> Color;
 }
 
+impl<T: Texture + ?Sized> Texture for Box<T> {
+    fn sample(&self, ray: &Ray, intersection: &RayIntersection) -> Color {
+        (**self).sample(ray, intersection)
+    }
+}
+
 impl Texture for Color {
     fn sample(&self, _: &Ray, _: &RayIntersection) -> Color {
         *self
@@ -57,6 +63,65 @@ impl<N: noise::NoiseFn<[f64; 3]>> Texture for Noise3D<N> {
     }
 }
 
+// ImageTexture:
+//
+// looks `intersection.uvs` up in a linear image, v goes up from the bottom row
+// `Framebuffer::open` reads and linearizes files
+//
+pub struct ImageTexture {
+    pub image: Framebuffer,
+    pub filter: Filter,
+    pub wrap: Wrap,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Wrap {
+    Repeat,
+    Mirror,
+    Clamp,
+}
+
+impl Wrap {
+    pub fn apply(self, i: i64, size: u32) -> u32 {
+        let size = size as i64;
+        let i = match self {
+            Wrap::Repeat => (i % size + size) % size,
+            Wrap::Mirror => {
+                let i = (i % (2 * size) + 2 * size) % (2 * size);
+                if i < size {
+                    i
+                } else {
+                    2 * size - 1 - i
+                }
+            }
+            Wrap::Clamp => i.max(0).min(size - 1),
+        };
+        i as u32
+    }
+}
+
+impl ImageTexture {
+    pub fn lookup(&self, u: Scalar, v: Scalar) -> Color {
+        self.image.lookup(u, v, self.filter, (self.wrap, self.wrap))
+    }
+}
+
+impl Texture for ImageTexture {
+    fn sample(&self, _: &Ray, intersection: &RayIntersection) -> Color {
+        if let Some(uvs) = intersection.uvs {
+            self.lookup(uvs.x, uvs.y)
+        } else {
+            Color::default()
+        }
+    }
+}
+
 // COMBINATORS
 
 pub struct Checkerboard<E: Texture, O: Texture> {
@@ -0,0 +1,108 @@
+// Tile:
+//
+// a rectangle of pixels that one thread renders at a time,
+// pixels close together in the image tend to touch the same objects
+//
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Tile {
+    // row by row
+    pub fn pixels(&self) -> impl Iterator<Item = (u32, u32)> {
+        let tile = *self;
+        (tile.y..tile.y + tile.height)
+            .flat_map(move |y| (tile.x..tile.x + tile.width).map(move |x| (x, y)))
+    }
+
+    pub fn area(&self) -> u32 {
+        self.width * self.height
+    }
+}
+
+// the order tiles are handed out in, threads pick them up as they finish their previous one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TileOrder {
+    Scanline,
+    // from the center outward, what matters most in a preview shows up first
+    Spiral,
+    // along a space filling curve, consecutive tiles stay next to each other
+    Hilbert,
+}
+
+impl Default for TileOrder {
+    fn default() -> Self {
+        TileOrder::Spiral
+    }
+}
+
+impl TileOrder {
+    // covers the image with `size` by `size` tiles, those on the right and bottom edges are cut short
+    pub fn tiles(self, width: u32, height: u32, size: u32) -> Vec<Tile> {
+        let size = size.max(1);
+        let columns = (width + size - 1) / size;
+        let rows = (height + size - 1) / size;
+        let mut cells: Vec<(u32, u32)> = (0..rows)
+            .flat_map(|y| (0..columns).map(move |x| (x, y)))
+            .collect();
+
+        match self {
+            TileOrder::Scanline => {}
+            TileOrder::Spiral => {
+                // by square ring around the center, then clockwise within each
+                let center_x = (columns as f32 - 1.) / 2.;
+                let center_y = (rows as f32 - 1.) / 2.;
+                let key = |&(x, y): &(u32, u32)| {
+                    let dx = x as f32 - center_x;
+                    let dy = y as f32 - center_y;
+                    (dx.abs().max(dy.abs()), dy.atan2(dx))
+                };
+                cells.sort_by(|a, b| {
+                    key(a)
+                        .partial_cmp(&key(b))
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                });
+            }
+            TileOrder::Hilbert => {
+                let side = columns.max(rows).next_power_of_two();
+                cells.sort_by_key(|&(x, y)| hilbert_index(side, x, y));
+            }
+        }
+
+        cells
+            .into_iter()
+            .map(|(x, y)| Tile {
+                x: x * size,
+                y: y * size,
+                width: size.min(width - x * size),
+                height: size.min(height - y * size),
+            })
+            .collect()
+    }
+}
+
+// distance along the Hilbert curve filling a `side` by `side` grid, `side` is a power of two
+fn hilbert_index(side: u32, mut x: u32, mut y: u32) -> u64 {
+    let mut index = 0;
+    let mut s = side / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as u32;
+        let ry = (y & s > 0) as u32;
+        index += s as u64 * s as u64 * ((3 * rx) ^ ry) as u64;
+
+        // turns the quadrant so the curve inside it starts and ends in the right corners
+        if ry == 0 {
+            if rx == 1 {
+                x = side - 1 - x;
+                y = side - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    index
+}
@@ -0,0 +1,91 @@
+use crate::prelude::*;
+
+// ToneMap:
+//
+// squeezes linear radiance into [0, 1] before it's encoded to 8-bit sRGB
+//
+pub trait ToneMap: std::fmt::Debug + Send + Sync {
+    fn map(&self, color: Color) -> Color;
+}
+
+// what captures looked like before tone mapping was a thing
+#[derive(Debug)]
+pub struct Clamp;
+
+impl ToneMap for Clamp {
+    fn map(&self, color: Color) -> Color {
+        use palette::Limited;
+
+        color.clamp()
+    }
+}
+
+#[derive(Debug)]
+pub struct Reinhard;
+
+impl ToneMap for Reinhard {
+    fn map(&self, color: Color) -> Color {
+        scale_luminance(color, |l| l / (1. + l))
+    }
+}
+
+// luminance at `white` and above maps to 1
+#[derive(Debug)]
+pub struct ExtendedReinhard {
+    pub white: Scalar,
+}
+
+impl ToneMap for ExtendedReinhard {
+    fn map(&self, color: Color) -> Color {
+        let white_squared = self.white * self.white;
+        scale_luminance(color, |l| l * (1. + l / white_squared) / (1. + l))
+    }
+}
+
+// Krzysztof Narkowicz's fit of the ACES reference rendering transform
+#[derive(Debug)]
+pub struct AcesFilmic;
+
+impl ToneMap for AcesFilmic {
+    fn map(&self, color: Color) -> Color {
+        let curve = |x: Scalar| {
+            let x = x * 0.6;
+            na::clamp(
+                (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+                0.,
+                1.,
+            )
+        };
+        Color::new(curve(color.red), curve(color.green), curve(color.blue))
+    }
+}
+
+// scales by 2^stops, then applies gamma on top of the sRGB curve
+#[derive(Debug)]
+pub struct Exposure {
+    pub stops: Scalar,
+    pub gamma: Scalar,
+}
+
+impl ToneMap for Exposure {
+    fn map(&self, color: Color) -> Color {
+        let scale = self.stops.exp2();
+        let curve = |x: Scalar| na::clamp(x * scale, 0., 1.).powf(1. / self.gamma);
+        Color::new(curve(color.red), curve(color.green), curve(color.blue))
+    }
+}
+
+fn scale_luminance<F: Fn(Scalar) -> Scalar>(color: Color, f: F) -> Color {
+    use palette::Limited;
+
+    let luminance = luminance(color);
+    if luminance <= 0. {
+        return Color::default();
+    }
+    (color * (f(luminance) / luminance)).clamp()
+}
+
+// Rec. 709 weights, for linear colors
+pub fn luminance(color: Color) -> Scalar {
+    0.2126 * color.red + 0.7152 * color.green + 0.0722 * color.blue
+}